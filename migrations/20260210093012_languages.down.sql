@@ -0,0 +1,9 @@
+-- Add down migration script here
+
+alter table attempts
+    drop column language_id;
+
+drop index assignment_languages_assignment_id_language_id_idx;
+
+drop table assignment_languages;
+drop table languages;
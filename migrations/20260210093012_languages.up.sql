@@ -0,0 +1,38 @@
+-- Add up migration script here
+
+create table languages (
+    id text not null primary key,
+
+    name text not null,
+    source_file text not null,
+
+    compile_command text,
+    run_command text not null,
+
+    memory_limit integer not null,
+    time_limit integer not null
+);
+
+create table assignment_languages (
+    id text not null primary key,
+
+    assignment_id text not null references assignments(id) on delete cascade on update cascade,
+    language_id text not null references languages(id) on delete cascade on update cascade
+);
+
+create unique index assignment_languages_assignment_id_language_id_idx on assignment_languages(assignment_id, language_id);
+
+insert into languages (id, name, source_file, compile_command, run_command, memory_limit, time_limit) values
+    (X'de37d083ed2945b8bc59b814fbdd7869', 'Python 3', 'main.py', null, '/usr/bin/python3 main.py', 512, 5),
+    (X'692ad2a9cf504f52b74f2d6abebb4b76', 'C (gcc)', 'main.c', '/usr/bin/gcc -O2 -std=c17 -o build/main main.c -lm', 'build/main', 256, 2),
+    (X'e37a5ae1c1714fb78b87c8936d1104ef', 'C++ (g++)', 'main.cpp', '/usr/bin/g++ -O2 -std=c++20 -o build/main main.cpp', 'build/main', 256, 2),
+    (X'94d9abfa9e0348738f67e5b9b1c0f4d2', 'Rust', 'main.rs', '/usr/bin/rustc -O -o build/main main.rs', 'build/main', 256, 2),
+    (X'ea54da6e6d734f4e9a5a3c6bfa81b2aa', 'Java', 'Main.java', '/usr/bin/javac -d build Main.java', '/usr/bin/java -cp build Main', 1024, 5);
+
+-- every existing assignment was implicitly python-only
+insert into assignment_languages (id, assignment_id, language_id)
+    select randomblob(16), id, X'de37d083ed2945b8bc59b814fbdd7869' from assignments;
+
+-- sqlite refuses to add a REFERENCES column with a non-null default to a populated table
+alter table attempts
+    add column language_id text not null default X'de37d083ed2945b8bc59b814fbdd7869';
@@ -0,0 +1,11 @@
+-- Add down migration script here
+
+drop index compilations_attempt_id_idx;
+
+drop table compilations;
+
+alter table languages
+    drop column compile_time_limit;
+
+alter table languages
+    drop column compile_memory_limit;
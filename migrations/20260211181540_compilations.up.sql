@@ -0,0 +1,30 @@
+-- Add up migration script here
+
+alter table languages
+    add column compile_memory_limit integer not null default 1024;
+
+alter table languages
+    add column compile_time_limit integer not null default 30;
+
+create table compilations (
+    id text not null primary key,
+
+    attempt_id text not null references attempts(id) on delete cascade on update cascade,
+
+    succeeded boolean not null default false,
+
+    command_ran text not null,
+    created_at timestamp not null,
+
+    finished_at timestamp,
+    exit_code integer,
+    stdout BLOB,
+    stderr BLOB,
+
+    memory_limit integer not null,
+    time_limit integer not null,
+    max_cpus integer not null,
+    disable_network boolean not null
+);
+
+create index compilations_attempt_id_idx on compilations(attempt_id);
@@ -0,0 +1,11 @@
+-- Add down migration script here
+
+alter table assignments drop column disable_network;
+alter table assignments drop column max_cpus;
+alter table assignments drop column time_limit;
+alter table assignments drop column memory_limit;
+
+alter table tests drop column disable_network;
+alter table tests drop column max_cpus;
+alter table tests drop column time_limit;
+alter table tests drop column memory_limit;
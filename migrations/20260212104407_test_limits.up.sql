@@ -0,0 +1,12 @@
+-- Add up migration script here
+
+-- null means "inherit from the assignment", which in turn inherits from the language
+alter table tests add column memory_limit integer;
+alter table tests add column time_limit integer;
+alter table tests add column max_cpus integer;
+alter table tests add column disable_network boolean;
+
+alter table assignments add column memory_limit integer;
+alter table assignments add column time_limit integer;
+alter table assignments add column max_cpus integer not null default 1;
+alter table assignments add column disable_network boolean not null default true;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+alter table runners
+    drop column verdict;
@@ -0,0 +1,8 @@
+-- Add up migration script here
+
+alter table runners
+    add column verdict text;
+
+update runners
+    set verdict = case when passed then 'accepted' else 'wrong_answer' end
+    where finished_at is not null;
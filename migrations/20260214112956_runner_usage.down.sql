@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+alter table runners drop column peak_memory_kb;
+alter table runners drop column cpu_time_ms;
+alter table runners drop column wall_time_ms;
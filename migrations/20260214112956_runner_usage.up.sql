@@ -0,0 +1,5 @@
+-- Add up migration script here
+
+alter table runners add column wall_time_ms integer;
+alter table runners add column cpu_time_ms integer;
+alter table runners add column peak_memory_kb integer;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+alter table compilations drop column sandbox_config;
+alter table runners drop column sandbox_config;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+
+alter table runners add column sandbox_config text;
+alter table compilations add column sandbox_config text;
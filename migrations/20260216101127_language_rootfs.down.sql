@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+alter table languages
+    drop column rootfs;
@@ -0,0 +1,9 @@
+-- Add up migration script here
+
+-- directory name below the configured rootfs root, e.g. an unpacked OCI image
+alter table languages
+    add column rootfs text not null default 'python';
+
+update languages set rootfs = 'gcc' where id in (X'692ad2a9cf504f52b74f2d6abebb4b76', X'e37a5ae1c1714fb78b87c8936d1104ef');
+update languages set rootfs = 'rust' where id = X'94d9abfa9e0348738f67e5b9b1c0f4d2';
+update languages set rootfs = 'java' where id = X'ea54da6e6d734f4e9a5a3c6bfa81b2aa';
@@ -0,0 +1,17 @@
+-- Add down migration script here
+
+alter table compilations add column disable_network boolean not null default true;
+update compilations set disable_network = network_policy = 'host';
+alter table compilations drop column network_policy;
+
+alter table runners add column disable_network boolean not null default true;
+update runners set disable_network = network_policy = 'host';
+alter table runners drop column network_policy;
+
+alter table assignments add column disable_network boolean not null default true;
+update assignments set disable_network = network_policy != 'host';
+alter table assignments drop column network_policy;
+
+alter table tests add column disable_network boolean;
+update tests set disable_network = network_policy != 'host' where network_policy is not null;
+alter table tests drop column network_policy;
@@ -0,0 +1,21 @@
+-- Add up migration script here
+
+-- `disable_network` was passed to nsjail as `--disable_clone_newnet`, which actually gave the
+-- jail the host network. Configured intent is kept, recorded runs keep what really happened.
+
+alter table tests add column network_policy text;
+update tests set network_policy = case when disable_network then 'isolated' else 'host' end
+    where disable_network is not null;
+alter table tests drop column disable_network;
+
+alter table assignments add column network_policy text not null default 'isolated';
+update assignments set network_policy = case when disable_network then 'isolated' else 'host' end;
+alter table assignments drop column disable_network;
+
+alter table runners add column network_policy text not null default 'isolated';
+update runners set network_policy = case when disable_network then 'host' else 'loopback' end;
+alter table runners drop column disable_network;
+
+alter table compilations add column network_policy text not null default 'isolated';
+update compilations set network_policy = case when disable_network then 'host' else 'loopback' end;
+alter table compilations drop column disable_network;
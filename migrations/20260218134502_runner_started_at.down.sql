@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+alter table compilations drop column started_at;
+alter table runners drop column started_at;
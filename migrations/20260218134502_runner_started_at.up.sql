@@ -0,0 +1,8 @@
+-- Add up migration script here
+
+-- runners and compilations are now created when queued, started_at is when a worker picked them up
+alter table runners add column started_at timestamp;
+update runners set started_at = created_at;
+
+alter table compilations add column started_at timestamp;
+update compilations set started_at = created_at;
@@ -0,0 +1,27 @@
+-- Add up migration script here
+
+-- every compilation and runner is executed through a job, so it survives restarts of the server
+create table jobs (
+    id blob primary key not null,
+    kind text not null,
+    attempt_id blob not null references attempts(id),
+    compilation_id blob references compilations(id),
+    runner_id blob references runners(id),
+
+    status text not null default 'queued',
+    tries integer not null default 0,
+    queued_at timestamp not null,
+
+    -- a running job whose lease ran out belongs to a crashed worker and is claimed again
+    leased_until timestamp,
+    heartbeat_at timestamp
+);
+
+create index jobs_status_queued_at on jobs (status, queued_at);
+create index jobs_runner_id on jobs (runner_id);
+
+-- runners that were left unfinished by the in-memory queue get another chance
+insert into jobs (id, kind, attempt_id, runner_id, queued_at)
+select randomblob(16), 'test', attempt_id, id, created_at
+from runners
+where finished_at is null;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+alter table compilations drop column error_reason;
+alter table runners drop column error_reason;
@@ -0,0 +1,5 @@
+-- Add up migration script here
+
+-- why the run could not be judged, kept while it is retried and once it is a system error
+alter table runners add column error_reason text;
+alter table compilations add column error_reason text;
@@ -0,0 +1,7 @@
+-- Add down migration script here
+
+drop index jobs_user_id_claimed_at;
+
+alter table jobs drop column claimed_at;
+alter table jobs drop column lane;
+alter table jobs drop column user_id;
@@ -0,0 +1,12 @@
+-- Add up migration script here
+
+-- jobs are handed out round-robin across users, lanes go before that
+alter table jobs add column user_id blob;
+update jobs set user_id = (select user_id from attempts where attempts.id = jobs.attempt_id);
+
+alter table jobs add column lane text not null default 'normal';
+
+alter table jobs add column claimed_at timestamp;
+update jobs set claimed_at = heartbeat_at;
+
+create index jobs_user_id_claimed_at on jobs (user_id, claimed_at);
@@ -0,0 +1,7 @@
+-- Add down migration script here
+
+drop index attempts_assignment_user;
+
+alter table assignments drop column daily_quota;
+alter table assignments drop column cooldown_seconds;
+alter table assignments drop column max_attempts;
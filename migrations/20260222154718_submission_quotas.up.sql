@@ -0,0 +1,8 @@
+-- Add up migration script here
+
+-- every limit is optional, null means unlimited
+alter table assignments add column max_attempts integer;
+alter table assignments add column cooldown_seconds integer;
+alter table assignments add column daily_quota integer;
+
+create index attempts_assignment_user on attempts (assignment_id, user_id, submitted_at);
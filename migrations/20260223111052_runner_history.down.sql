@@ -0,0 +1,3 @@
+-- Add down migration script here
+
+drop index runners_attempt_test_created;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+
+-- rejudging adds runners next to the old ones, only the newest of every test counts
+create index runners_attempt_test_created on runners (attempt_id, test_id, created_at);
@@ -0,0 +1,4 @@
+-- Add up migration script here
+
+-- name of the evaltor-worker holding the job, null for the server's own judge slots
+alter table jobs add column worker text;
@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+drop table class_members;
+
+alter table users drop column is_admin;
@@ -0,0 +1,21 @@
+-- Add up migration script here
+
+alter table users add column is_admin boolean not null default false;
+
+create table class_members (
+    class_id blob not null references classes(id) on delete cascade on update cascade,
+    user_id blob not null references users(id) on delete cascade on update cascade,
+    -- teacher, ta or student
+    role text not null,
+
+    primary key (class_id, user_id)
+);
+
+create index class_members_user_id on class_members (user_id);
+
+-- creators teach their classes, everyone assigned something in a class is a student of it
+insert into class_members (class_id, user_id, role)
+select id, creator_id, 'teacher' from classes;
+
+insert or ignore into class_members (class_id, user_id, role)
+select distinct class_id, user_id, 'student' from user_assignments;
@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+drop index classes_join_code;
+
+alter table classes drop column join_code;
@@ -0,0 +1,7 @@
+-- Add up migration script here
+
+alter table classes add column join_code text;
+
+update classes set join_code = upper(hex(randomblob(4)));
+
+create unique index classes_join_code on classes (join_code);
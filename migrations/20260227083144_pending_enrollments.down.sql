@@ -0,0 +1,7 @@
+-- Add down migration script here
+
+drop table pending_assignments;
+
+drop table pending_enrollments;
+
+alter table users drop column student_id;
@@ -0,0 +1,23 @@
+-- Add up migration script here
+
+alter table users add column student_id text;
+
+-- students imported from a roster before their first login
+create table pending_enrollments (
+    class_id blob not null references classes(id) on delete cascade on update cascade,
+    -- lowercase, matched against the verified email of whoever signs in
+    email text not null,
+    name text not null,
+    student_id text,
+
+    primary key (class_id, email)
+);
+
+create table pending_assignments (
+    class_id blob not null,
+    email text not null,
+    assignment_id blob not null references assignments(id) on delete cascade on update cascade,
+
+    primary key (class_id, email, assignment_id),
+    foreign key (class_id, email) references pending_enrollments(class_id, email) on delete cascade on update cascade
+);
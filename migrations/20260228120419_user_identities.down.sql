@@ -0,0 +1,32 @@
+-- Add down migration script here
+
+-- rebuilt with foreign keys off like in the up migration
+create table new_users (
+    id text not null primary key,
+    -- users without a Google login get a placeholder that matches no one
+    google_sub text not null unique,
+    email text not null,
+    name text not null,
+    is_admin boolean not null default false,
+    student_id text
+);
+
+insert into new_users (id, google_sub, email, name, is_admin, student_id)
+select
+    u.id,
+    coalesce(
+        (select i.subject from user_identities i
+        where i.user_id = u.id and i.issuer = 'https://accounts.google.com'),
+        'unlinked:' || hex(u.id)
+    ),
+    u.email,
+    u.name,
+    u.is_admin,
+    u.student_id
+from users u;
+
+drop table user_identities;
+
+drop table users;
+
+alter table new_users rename to users;
@@ -0,0 +1,33 @@
+-- Add up migration script here
+
+create table user_identities (
+    -- `iss` claim of the identity provider
+    issuer text not null,
+    -- `sub` claim, unique within the issuer
+    subject text not null,
+    user_id blob not null references users(id) on delete cascade on update cascade,
+
+    primary key (issuer, subject)
+);
+
+create index user_identities_user_id on user_identities (user_id);
+
+insert into user_identities (issuer, subject, user_id)
+select 'https://accounts.google.com', google_sub, id from users;
+
+-- google_sub is unique, so users is rebuilt without it. This relies on migrations running with
+-- foreign keys off, dropping users would cascade to everything referencing it otherwise.
+create table new_users (
+    id text not null primary key,
+    email text not null,
+    name text not null,
+    is_admin boolean not null default false,
+    student_id text
+);
+
+insert into new_users (id, email, name, is_admin, student_id)
+select id, email, name, is_admin, student_id from users;
+
+drop table users;
+
+alter table new_users rename to users;
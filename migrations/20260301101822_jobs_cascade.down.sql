@@ -0,0 +1,38 @@
+-- Add down migration script here
+
+create table old_jobs (
+    id blob primary key not null,
+    kind text not null,
+    attempt_id blob not null references attempts(id),
+    compilation_id blob references compilations(id),
+    runner_id blob references runners(id),
+
+    status text not null default 'queued',
+    tries integer not null default 0,
+    queued_at timestamp not null,
+
+    -- a running job whose lease ran out belongs to a crashed worker and is claimed again
+    leased_until timestamp,
+    heartbeat_at timestamp,
+    user_id blob,
+    lane text not null default 'normal',
+    claimed_at timestamp,
+    worker text
+);
+
+insert into old_jobs (
+    id, kind, attempt_id, compilation_id, runner_id, status, tries, queued_at,
+    leased_until, heartbeat_at, user_id, lane, claimed_at, worker
+)
+select
+    id, kind, attempt_id, compilation_id, runner_id, status, tries, queued_at,
+    leased_until, heartbeat_at, user_id, lane, claimed_at, worker
+from jobs;
+
+drop table jobs;
+
+alter table old_jobs rename to jobs;
+
+create index jobs_status_queued_at on jobs (status, queued_at);
+create index jobs_runner_id on jobs (runner_id);
+create index jobs_user_id_claimed_at on jobs (user_id, claimed_at);
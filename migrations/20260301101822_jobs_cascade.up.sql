@@ -0,0 +1,44 @@
+-- Add up migration script here
+
+-- jobs go with the attempt, compilation or runner they run, and keep their ids as text like
+-- every other table. Rebuilt with foreign keys off, see user_identities.
+create table new_jobs (
+    id text not null primary key,
+    kind text not null,
+    attempt_id text not null references attempts(id) on delete cascade on update cascade,
+    user_id text not null references users(id) on delete cascade on update cascade,
+    lane text not null default 'normal',
+    compilation_id text references compilations(id) on delete cascade on update cascade,
+    runner_id text references runners(id) on delete cascade on update cascade,
+
+    status text not null default 'queued',
+    tries integer not null default 0,
+    queued_at timestamp not null,
+    claimed_at timestamp,
+    -- name of the evaltor-worker holding the job, null for the server's own judge slots
+    worker text,
+
+    -- a running job whose lease ran out belongs to a crashed worker and is claimed again
+    leased_until timestamp,
+    heartbeat_at timestamp
+);
+
+-- jobs of deleted attempts are dropped, they would have gone with them
+insert into new_jobs (
+    id, kind, attempt_id, user_id, lane, compilation_id, runner_id,
+    status, tries, queued_at, claimed_at, worker, leased_until, heartbeat_at
+)
+select
+    j.id, j.kind, j.attempt_id, a.user_id, j.lane, j.compilation_id, j.runner_id,
+    j.status, j.tries, j.queued_at, j.claimed_at, j.worker, j.leased_until, j.heartbeat_at
+from jobs j
+join attempts a on a.id = j.attempt_id;
+
+drop table jobs;
+
+alter table new_jobs rename to jobs;
+
+create index jobs_status_queued_at on jobs (status, queued_at);
+create index jobs_runner_id on jobs (runner_id);
+create index jobs_user_id_claimed_at on jobs (user_id, claimed_at);
+create index jobs_attempt_id on jobs (attempt_id);
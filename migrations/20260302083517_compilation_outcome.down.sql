@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+update compilations set error_reason = 'cancelled' where outcome = 'cancelled';
+
+alter table compilations drop column outcome;
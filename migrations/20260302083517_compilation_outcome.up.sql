@@ -0,0 +1,15 @@
+-- Add up migration script here
+
+-- how the build ended, null while it is queued or running
+alter table compilations add column outcome text;
+
+update compilations set outcome = case
+    when succeeded then 'succeeded'
+    when error_reason = 'cancelled' then 'cancelled'
+    when error_reason is not null then 'system_error'
+    else 'failed'
+end
+where finished_at is not null;
+
+-- cancellations were recorded as the error reason before
+update compilations set error_reason = null where outcome = 'cancelled';
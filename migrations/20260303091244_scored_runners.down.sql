@@ -0,0 +1,3 @@
+-- Add down migration script here
+
+drop view scored_runners;
@@ -0,0 +1,17 @@
+-- Add up migration script here
+
+-- the run of every test of an attempt that counts: the newest one that finished with a verdict
+-- about the program. Queued, cancelled and failed rejudges leave the previous run in place.
+create view scored_runners as
+select r.*
+from runners r
+where r.finished_at is not null
+and r.verdict not in ('cancelled', 'system_error')
+and not exists (
+    select 1 from runners r2
+    where r2.attempt_id = r.attempt_id
+    and r2.test_id = r.test_id
+    and r2.finished_at is not null
+    and r2.verdict not in ('cancelled', 'system_error')
+    and r2.created_at > r.created_at
+);
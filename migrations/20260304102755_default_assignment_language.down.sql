@@ -0,0 +1,3 @@
+-- Add down migration script here
+
+drop trigger assignments_default_language;
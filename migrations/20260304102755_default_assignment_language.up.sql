@@ -0,0 +1,14 @@
+-- Add up migration script here
+
+-- new assignments start out Python-only like the ones before languages, so they can be
+-- submitted to until more languages are enabled in assignment_languages
+create trigger assignments_default_language after insert on assignments
+begin
+    insert into assignment_languages (id, assignment_id, language_id)
+        values (randomblob(16), new.id, X'de37d083ed2945b8bc59b814fbdd7869');
+end;
+
+-- assignments created since the languages migration have none
+insert into assignment_languages (id, assignment_id, language_id)
+    select randomblob(16), a.id, X'de37d083ed2945b8bc59b814fbdd7869' from assignments a
+    where not exists (select 1 from assignment_languages al where al.assignment_id = a.id);
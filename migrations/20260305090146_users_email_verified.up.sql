@@ -0,0 +1,5 @@
+-- Add up migration script here
+
+-- whether the provider of the last login vouched for the email, only then does an imported
+-- roster enroll the user under it. Everyone is unverified until they sign in again.
+alter table users add column email_verified boolean not null default false;
@@ -1,6 +1,8 @@
 use std::path::PathBuf;
 
-use clap::Parser;
+use clap::{ArgAction, Parser};
+
+use crate::sandbox::SandboxArgs;
 
 #[derive(Clone, Debug, Parser)]
 pub struct EvaltorArgs {
@@ -12,6 +14,35 @@ pub struct EvaltorArgs {
     #[clap(short, long, env = "EVALTOR_TESTS")]
     pub tests: PathBuf,
 
+    #[clap(flatten)]
+    pub sandbox: SandboxArgs,
+
+    /// Number of sandboxes the server itself may run at the same time, everything else waits
+    /// in a queue. With 0 all judging is left to `evaltor-worker` processes.
+    #[clap(long, env = "EVALTOR_JUDGE_WORKERS", default_value_t = 2)]
+    pub judge_workers: usize,
+
+    /// Pin every judge worker to its own CPU, worker N runs on CPU N
+    #[clap(long, env = "EVALTOR_PIN_CPUS")]
+    pub pin_cpus: bool,
+
+    /// Cancel the unfinished evaluation of earlier attempts when a user submits again
+    #[clap(long, env = "EVALTOR_CANCEL_SUPERSEDED", default_value_t = true, action = ArgAction::Set)]
+    pub cancel_superseded: bool,
+
+    /// Verified emails of users made administrators when they log in, administrators teach
+    /// every class
+    #[clap(long = "admin", env = "EVALTOR_ADMINS", value_delimiter = ',')]
+    pub admins: Vec<String>,
+
+    /// Seconds a shutdown waits for running evaluations before killing and requeueing them
+    #[clap(long, env = "EVALTOR_SHUTDOWN_TIMEOUT", default_value_t = 30)]
+    pub shutdown_timeout: u64,
+
+    /// Secret `evaltor-worker` processes authenticate with, remote workers are refused without it
+    #[clap(long, env = "EVALTOR_WORKER_SECRET")]
+    pub worker_secret: Option<String>,
+
     /// Hostname
     #[clap(long, env = "EVALTOR_HOSTNAME")]
     pub hostname: String,
@@ -20,11 +51,26 @@ pub struct EvaltorArgs {
     #[clap(long, env = "EVALTOR_PORT")]
     pub port: u16,
 
-    /// Google Client ID
+    /// Identity providers users sign in with. Each is configured by `EVALTOR_OIDC_<ID>_ISSUER`,
+    /// `_CLIENT_ID` and `_CLIENT_SECRET`, and optionally `_SCOPES`, `_LABEL` and `_TRUST_EMAIL`,
+    /// set to true for providers that only give out verified addresses but do not say so
+    #[clap(
+        long = "oidc-provider",
+        env = "EVALTOR_OIDC_PROVIDERS",
+        value_delimiter = ','
+    )]
+    pub oidc_providers: Vec<String>,
+
+    /// Sign in as any local user picked on the login page, without an identity provider.
+    /// Only for development, anyone who can reach the server becomes whoever they like
+    #[clap(long, env = "EVALTOR_DEV_AUTH")]
+    pub dev_auth: bool,
+
+    /// Google Client ID, used when no `--oidc-provider` is given
     #[clap(long, env = "EVALTOR_GOOGLE_CLIENT_ID")]
-    pub google_client_id: String,
+    pub google_client_id: Option<String>,
 
-    /// Google Client Secret
+    /// Google Client Secret, used when no `--oidc-provider` is given
     #[clap(long, env = "EVALTOR_GOOGLE_CLIENT_SECRET")]
-    pub google_client_secret: String,
+    pub google_client_secret: Option<String>,
 }
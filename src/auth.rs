@@ -2,11 +2,11 @@ use std::{io, ops::Deref};
 
 use askama::Template;
 use axum::{
-    Router,
-    extract::{FromRequestParts, Query, State},
+    Form, Router,
+    extract::{FromRequestParts, Path, Query, RawPathParams, State},
     http::{StatusCode, request::Parts},
     response::{Html, IntoResponse, Redirect, Response},
-    routing::get,
+    routing::{get, post},
 };
 use openidconnect::{
     AuthenticationFlow, AuthorizationCode, ClientId, ClientSecret, CsrfToken, EndpointMaybeSet,
@@ -17,7 +17,11 @@ use serde::Deserialize;
 use tower_sessions::Session;
 use uuid::Uuid;
 
-use crate::{models::User, state::EvaltorState};
+use crate::{
+    EvaltorArgs,
+    models::{Class, ClassMember, ClassRole, PendingEnrollment, User},
+    state::EvaltorState,
+};
 
 const GOOGLE_ISSUER_URL: &str = "https://accounts.google.com";
 
@@ -54,15 +58,12 @@ impl FromRequestParts<EvaltorState> for AuthUser {
         let user_id = session
             .get::<Uuid>("user_id")
             .await
-            .inspect_err(|e| {
-                dbg!(e);
-            })
             .map_err(|_| Redirect::to("/login").into_response())?
             .ok_or_else(|| Redirect::to("/login").into_response())?;
 
         let user = sqlx::query_as!(
             User,
-            r#"SELECT id as "id: uuid::Uuid", google_sub, email, name FROM users WHERE id = ?"#,
+            r#"SELECT id as "id: uuid::Uuid", email, name, is_admin, student_id FROM users WHERE id = ?"#,
             user_id
         )
         .fetch_optional(&state.db_pool)
@@ -74,12 +75,198 @@ impl FromRequestParts<EvaltorState> for AuthUser {
     }
 }
 
-pub async fn build_oidc_client(
-    hostname: String,
+/// Role of the user in the class, administrators are teachers of every class.
+pub async fn class_role(
+    db: &sqlx::SqlitePool,
+    class_id: Uuid,
+    user: &User,
+) -> sqlx::Result<Option<ClassRole>> {
+    if user.is_admin {
+        return Ok(Some(ClassRole::Teacher));
+    }
+
+    ClassMember::role_of(db, class_id, user.id).await
+}
+
+/// A teacher of the class in the `class_id` path parameter.
+pub struct TeacherOf {
+    pub user: User,
+    pub class_id: Uuid,
+}
+
+impl FromRequestParts<EvaltorState> for TeacherOf {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &EvaltorState,
+    ) -> Result<Self, Self::Rejection> {
+        let (user, class_id) = member_of(parts, state, ClassRole::Teacher).await?;
+
+        Ok(Self { user, class_id })
+    }
+}
+
+/// A teacher or teaching assistant of the class in the `class_id` path parameter.
+pub struct StaffOf {
+    pub user: User,
+    pub class_id: Uuid,
+}
+
+impl FromRequestParts<EvaltorState> for StaffOf {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &EvaltorState,
+    ) -> Result<Self, Self::Rejection> {
+        let (user, class_id) = member_of(parts, state, ClassRole::Ta).await?;
+
+        Ok(Self { user, class_id })
+    }
+}
+
+/// Authenticates the user and checks they have at least `min_role` in the class in the path.
+async fn member_of(
+    parts: &mut Parts,
+    state: &EvaltorState,
+    min_role: ClassRole,
+) -> Result<(User, Uuid), Response> {
+    let AuthUser(user) = AuthUser::from_request_parts(parts, state).await?;
+
+    let params = RawPathParams::from_request_parts(parts, state)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    let class_id = params
+        .iter()
+        .find(|(key, _)| *key == "class_id")
+        .and_then(|(_, value)| Uuid::parse_str(value).ok())
+        .ok_or_else(|| StatusCode::NOT_FOUND.into_response())?;
+
+    let role = class_role(&state.db_pool, class_id, &user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
+
+    if role.is_none_or(|role| role < min_role) {
+        return Err(StatusCode::FORBIDDEN.into_response());
+    }
+
+    Ok((user, class_id))
+}
+
+/// An OIDC identity provider users sign in with.
+pub struct OidcProvider {
+    /// Names the provider in URLs and configuration
+    pub id: String,
+    /// Shown on its sign in button
+    pub label: String,
+    client: DiscoveredClient,
+    scopes: Vec<String>,
+    /// Takes the email of a login as verified when the provider does not say whether it is
+    trust_email: bool,
+}
+
+impl OidcProvider {
+    /// Discovers the provider configured by the `EVALTOR_OIDC_<ID>_*` variables.
+    async fn from_env(id: &str, hostname: &str) -> Result<Self, io::Error> {
+        let prefix = format!(
+            "EVALTOR_OIDC_{}_",
+            id.to_uppercase().replace(['-', '.'], "_")
+        );
+
+        let var = |name: &str| {
+            std::env::var(format!("{prefix}{name}"))
+                .map_err(|_| io::Error::other(format!("{prefix}{name} is not set")))
+        };
+
+        let scopes = var("SCOPES").map_or_else(
+            |_| DEFAULT_SCOPES.map(str::to_owned).to_vec(),
+            |scopes| {
+                scopes
+                    .split([',', ' '])
+                    .filter(|scope| !scope.is_empty())
+                    .map(str::to_owned)
+                    .collect()
+            },
+        );
+
+        let trust_email = match var("TRUST_EMAIL") {
+            Ok(trust) => trust.parse().map_err(|_| {
+                io::Error::other(format!("{prefix}TRUST_EMAIL is not true or false"))
+            })?,
+            Err(_) => false,
+        };
+
+        Ok(Self {
+            id: id.to_owned(),
+            label: var("LABEL").unwrap_or_else(|_| id.to_owned()),
+            client: discover(
+                hostname,
+                var("ISSUER")?,
+                var("CLIENT_ID")?,
+                var("CLIENT_SECRET")?,
+            )
+            .await?,
+            scopes,
+            trust_email,
+        })
+    }
+}
+
+/// Scopes asked for when a provider does not configure its own, `openid` is always added.
+const DEFAULT_SCOPES: [&str; 2] = ["email", "profile"];
+
+/// Discovers every provider in `--oidc-provider`, or just Google with the `--google-client-*`
+/// credentials when there are none and `--dev-auth` is off.
+pub async fn build_oidc_providers(args: &EvaltorArgs) -> Result<Vec<OidcProvider>, io::Error> {
+    let mut providers = Vec::new();
+
+    for id in &args.oidc_providers {
+        // the other routes under /auth would shadow its login
+        if id.is_empty() || ["callback", "dev", "logout"].contains(&id.as_str()) {
+            return Err(io::Error::other(format!("{id:?} cannot name a provider")));
+        }
+
+        providers.push(OidcProvider::from_env(id, &args.hostname).await?);
+    }
+
+    // development needs no provider, nor the network to discover one
+    if providers.is_empty() && !args.dev_auth {
+        let (Some(client_id), Some(client_secret)) =
+            (&args.google_client_id, &args.google_client_secret)
+        else {
+            return Err(io::Error::other(
+                "no identity provider is configured, see --oidc-provider",
+            ));
+        };
+
+        providers.push(OidcProvider {
+            id: "google".to_owned(),
+            label: "Google".to_owned(),
+            client: discover(
+                &args.hostname,
+                GOOGLE_ISSUER_URL.to_owned(),
+                client_id.clone(),
+                client_secret.clone(),
+            )
+            .await?,
+            scopes: DEFAULT_SCOPES.map(str::to_owned).to_vec(),
+            // Google says whether every address is verified
+            trust_email: false,
+        });
+    }
+
+    Ok(providers)
+}
+
+async fn discover(
+    hostname: &str,
+    issuer: String,
     client_id: String,
     client_secret: String,
 ) -> Result<DiscoveredClient, io::Error> {
-    let issuer_url = IssuerUrl::new(GOOGLE_ISSUER_URL.to_owned()).map_err(io::Error::other)?;
+    let issuer_url = IssuerUrl::new(issuer).map_err(io::Error::other)?;
 
     let http_client = reqwest::ClientBuilder::new()
         .redirect(reqwest::redirect::Policy::none())
@@ -102,26 +289,65 @@ pub async fn build_oidc_client(
     Ok(client)
 }
 
+fn provider<'a>(state: &'a EvaltorState, id: &str) -> Option<&'a OidcProvider> {
+    state
+        .oidc_providers
+        .iter()
+        .find(|provider| provider.id == id)
+}
+
 #[derive(Deserialize)]
 struct AuthCallbackParams {
     code: String,
     state: String,
 }
 
-async fn google_login(
+async fn provider_login(
     State(state): State<EvaltorState>,
     session: Session,
+    Path(provider_id): Path<String>,
 ) -> Result<Redirect, StatusCode> {
-    let (auth_url, csrf_token, nonce) = state
-        .oidc_client
-        .authorize_url(
-            AuthenticationFlow::<CoreResponseType>::AuthorizationCode,
-            CsrfToken::new_random,
-            Nonce::new_random,
-        )
-        .add_scope(Scope::new("email".to_owned()))
-        .add_scope(Scope::new("profile".to_owned()))
-        .url();
+    start_login(&state, &session, &provider_id).await
+}
+
+/// Signs in with another provider and links the login to the current user.
+async fn provider_link(
+    _auth: AuthUser,
+    State(state): State<EvaltorState>,
+    session: Session,
+    Path(provider_id): Path<String>,
+) -> Result<Redirect, StatusCode> {
+    session
+        .insert("linking", true)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    start_login(&state, &session, &provider_id).await
+}
+
+async fn start_login(
+    state: &EvaltorState,
+    session: &Session,
+    provider_id: &str,
+) -> Result<Redirect, StatusCode> {
+    let provider = provider(state, provider_id).ok_or(StatusCode::NOT_FOUND)?;
+
+    let mut request = provider.client.authorize_url(
+        AuthenticationFlow::<CoreResponseType>::AuthorizationCode,
+        CsrfToken::new_random,
+        Nonce::new_random,
+    );
+
+    for scope in &provider.scopes {
+        request = request.add_scope(Scope::new(scope.clone()));
+    }
+
+    let (auth_url, csrf_token, nonce) = request.url();
+
+    session
+        .insert("oidc_provider", &provider.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     session
         .insert("csrf_token", csrf_token.secret().clone())
@@ -157,13 +383,21 @@ async fn auth_callback(
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .ok_or(StatusCode::BAD_REQUEST)?;
 
+    let provider_id: String = session
+        .get("oidc_provider")
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::BAD_REQUEST)?;
+
+    let provider = provider(&state, &provider_id).ok_or(StatusCode::BAD_REQUEST)?;
+
     let http_client = reqwest::ClientBuilder::new()
         .redirect(reqwest::redirect::Policy::none())
         .build()
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    let token_response = state
-        .oidc_client
+    let token_response = provider
+        .client
         .exchange_code(AuthorizationCode::new(params.code))
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .request_async(&http_client)
@@ -176,12 +410,13 @@ async fn auth_callback(
 
     let claims = id_token
         .claims(
-            &state.oidc_client.id_token_verifier(),
+            &provider.client.id_token_verifier(),
             &Nonce::new(stored_nonce),
         )
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    let google_sub = claims.subject().to_string();
+    let issuer = claims.issuer().as_str().to_owned();
+    let subject = claims.subject().to_string();
 
     let email = claims
         .email()
@@ -194,32 +429,287 @@ async fn auth_callback(
         .map(|n| n.as_str().to_owned())
         .unwrap_or_default();
 
-    let existing_id = sqlx::query_scalar!(
-        r#"SELECT id as "id: uuid::Uuid" FROM users WHERE google_sub = ?"#,
-        google_sub
+    let linking = session
+        .remove::<bool>("linking")
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .unwrap_or_default();
+
+    let existing_id = identity_owner(&state.db_pool, &issuer, &subject)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let signed_in = session
+        .get::<Uuid>("user_id")
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if linking {
+        let user_id = signed_in.ok_or(StatusCode::BAD_REQUEST)?;
+
+        return link_login(&state.db_pool, user_id, existing_id, &issuer, &subject)
+            .await
+            .map(|()| Redirect::to("/"));
+    }
+
+    let email_verified = vouches_for_email(claims.email_verified(), provider.trust_email);
+
+    let user_id = sign_in(
+        &state.db_pool,
+        existing_id,
+        &issuer,
+        &subject,
+        &email,
+        &name,
+        email_verified,
     )
-    .fetch_optional(&state.db_pool)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    let user_id = if let Some(id) = existing_id {
+    // only an address the provider vouches for may take over what was prepared for it
+    if email_verified {
+        grant_by_email(&state, user_id, &email)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    } else {
+        eprintln!(
+            "Not matching {email} of user {user_id} to enrollments and administrators, \
+            {provider_id} does not say it is verified, see EVALTOR_OIDC_<ID>_TRUST_EMAIL"
+        );
+    }
+
+    session
+        .insert("user_id", user_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Redirect::to("/"))
+}
+
+/// Whether the email of a login is its owner's, by the provider's `email_verified` claim or,
+/// when it has none, by the trust configured for the provider.
+const fn vouches_for_email(email_verified: Option<bool>, trust_email: bool) -> bool {
+    match email_verified {
+        Some(verified) => verified,
+        None => trust_email,
+    }
+}
+
+/// Links the login to the signed in user, unless it already belongs to someone else.
+async fn link_login(
+    db: &sqlx::SqlitePool,
+    user_id: Uuid,
+    existing_id: Option<Uuid>,
+    issuer: &str,
+    subject: &str,
+) -> Result<(), StatusCode> {
+    match existing_id {
+        Some(id) if id == user_id => Ok(()),
+        Some(_) => Err(StatusCode::CONFLICT),
+        None => link_identity(db, user_id, issuer, subject)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+/// User the login with the provider belongs to.
+async fn identity_owner(
+    db: &sqlx::SqlitePool,
+    issuer: &str,
+    subject: &str,
+) -> sqlx::Result<Option<Uuid>> {
+    sqlx::query_scalar!(
+        r#"SELECT user_id as "user_id: uuid::Uuid" FROM user_identities WHERE issuer = ? AND subject = ?"#,
+        issuer,
+        subject
+    )
+    .fetch_optional(db)
+    .await
+}
+
+/// Refreshes the user the identity belongs to, or creates one for a new identity.
+///
+/// `email_verified` records whether the provider vouched for the email, only such an email
+/// enrolls the user when a roster with it is imported.
+async fn sign_in(
+    db: &sqlx::SqlitePool,
+    existing_id: Option<Uuid>,
+    issuer: &str,
+    subject: &str,
+    email: &str,
+    name: &str,
+    email_verified: bool,
+) -> sqlx::Result<Uuid> {
+    if let Some(id) = existing_id {
         sqlx::query!(
-            "UPDATE users SET email = ?, name = ? WHERE google_sub = ?",
+            "UPDATE users SET email = ?, name = ?, email_verified = ? WHERE id = ?",
             email,
             name,
-            google_sub
+            email_verified,
+            id
         )
-        .execute(&state.db_pool)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .execute(db)
+        .await?;
+
+        return Ok(id);
+    }
+
+    let new_id = Uuid::new_v4();
 
+    sqlx::query!(
+        "INSERT INTO users (id, email, name, email_verified) VALUES (?, ?, ?, ?)",
+        new_id,
+        email,
+        name,
+        email_verified
+    )
+    .execute(db)
+    .await?;
+
+    link_identity(db, new_id, issuer, subject).await?;
+
+    Ok(new_id)
+}
+
+/// Enrolls the user in the classes they were imported into and makes configured administrators
+/// administrators.
+async fn grant_by_email(state: &EvaltorState, user_id: Uuid, email: &str) -> sqlx::Result<()> {
+    PendingEnrollment::claim(&state.db_pool, user_id, email).await?;
+
+    if state.config.admins.iter().any(|admin| admin == email) {
+        sqlx::query!("UPDATE users SET is_admin = true WHERE id = ?", user_id)
+            .execute(&state.db_pool)
+            .await?;
+    }
+
+    Ok(())
+}
+
+async fn link_identity(
+    db: &sqlx::SqlitePool,
+    user_id: Uuid,
+    issuer: &str,
+    subject: &str,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        "INSERT INTO user_identities (issuer, subject, user_id) VALUES (?, ?, ?)",
+        issuer,
+        subject,
+        user_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+#[derive(Template)]
+#[template(path = "login.html")]
+struct LoginPage {
+    /// Ids and labels of the providers to sign in with
+    providers: Vec<(String, String)>,
+    /// Users and classes to pick from with `--dev-auth`
+    dev: Option<(Vec<User>, Vec<Class>)>,
+}
+
+async fn login(State(state): State<EvaltorState>) -> Result<Html<String>, StatusCode> {
+    let dev = if state.config.dev_auth {
+        let users = User::all(&state.db_pool)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        let classes = Class::all(&state.db_pool)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Some((users, classes))
+    } else {
+        None
+    };
+
+    LoginPage {
+        providers: providers(&state),
+        dev,
+    }
+    .render()
+    .map(Html)
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Role a development user signs in with, see `--dev-auth`.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum DevRole {
+    Student,
+    Ta,
+    Teacher,
+    Admin,
+}
+
+#[derive(Deserialize)]
+struct DevLoginForm {
+    email: String,
+    #[serde(default)]
+    name: String,
+    role: DevRole,
+    /// Class the role is given in, ignored for administrators
+    #[serde(default, deserialize_with = "empty_as_none")]
+    class_id: Option<Uuid>,
+}
+
+/// Reads the empty value of an unselected `<select>` as `None`.
+fn empty_as_none<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Uuid>, D::Error> {
+    let value = String::deserialize(deserializer)?;
+
+    if value.is_empty() {
+        Ok(None)
+    } else {
+        Uuid::parse_str(&value)
+            .map(Some)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Signs in as the user with the email, created if there is none, with the chosen role.
+async fn dev_login(
+    State(state): State<EvaltorState>,
+    session: Session,
+    Form(form): Form<DevLoginForm>,
+) -> Result<Redirect, StatusCode> {
+    if !state.config.dev_auth {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let email = form.email.trim().to_owned();
+
+    if email.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let existing_id = sqlx::query_scalar!(
+        r#"SELECT id as "id: uuid::Uuid" FROM users WHERE email = ?"#,
+        email
+    )
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let user_id = if let Some(id) = existing_id {
         id
     } else {
-        let new_id = Uuid::new_v4();
+        let id = Uuid::new_v4();
+        let name = if form.name.trim().is_empty() {
+            email.clone()
+        } else {
+            form.name.trim().to_owned()
+        };
+
+        // development users own whatever email they type
         sqlx::query!(
-            "INSERT INTO users (id, google_sub, email, name) VALUES (?, ?, ?, ?)",
-            new_id,
-            google_sub,
+            "INSERT INTO users (id, email, name, email_verified) VALUES (?, ?, ?, true)",
+            id,
             email,
             name
         )
@@ -227,9 +717,37 @@ async fn auth_callback(
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-        new_id
+        id
+    };
+
+    let is_admin = form.role == DevRole::Admin;
+
+    sqlx::query!(
+        "UPDATE users SET is_admin = ? WHERE id = ?",
+        is_admin,
+        user_id
+    )
+    .execute(&state.db_pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let class_role = match form.role {
+        DevRole::Student => Some(ClassRole::Student),
+        DevRole::Ta => Some(ClassRole::Ta),
+        DevRole::Teacher => Some(ClassRole::Teacher),
+        DevRole::Admin => None,
     };
 
+    if let (Some(class_id), Some(role)) = (form.class_id, class_role) {
+        ClassMember::set_role(&state.db_pool, class_id, user_id, role)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    }
+
+    grant_by_email(&state, user_id, &email)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
     session
         .insert("user_id", user_id)
         .await
@@ -238,17 +756,6 @@ async fn auth_callback(
     Ok(Redirect::to("/"))
 }
 
-#[derive(Template)]
-#[template(path = "login.html")]
-struct LoginPage;
-
-async fn login() -> Result<Html<String>, StatusCode> {
-    LoginPage
-        .render()
-        .map(Html)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
-}
-
 async fn logout(session: Session) -> Result<Redirect, StatusCode> {
     session
         .flush()
@@ -257,10 +764,297 @@ async fn logout(session: Session) -> Result<Redirect, StatusCode> {
     Ok(Redirect::to("/login"))
 }
 
+/// Ids and labels of the configured providers, for their sign in buttons.
+pub fn providers(state: &EvaltorState) -> Vec<(String, String)> {
+    state
+        .oidc_providers
+        .iter()
+        .map(|provider| (provider.id.clone(), provider.label.clone()))
+        .collect()
+}
+
 pub fn auth_router() -> Router<EvaltorState> {
     Router::new()
         .route("/login", get(login))
-        .route("/auth/google", get(google_login))
+        .route("/auth/{provider}", get(provider_login))
+        .route("/auth/{provider}/link", get(provider_link))
         .route("/auth/callback", get(auth_callback))
+        .route("/auth/dev", post(dev_login))
         .route("/auth/logout", get(logout))
 }
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode, header},
+    };
+    use sqlx::SqlitePool;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use super::{
+        DEFAULT_SCOPES, OidcProvider, discover, identity_owner, link_login, sign_in,
+        vouches_for_email,
+    };
+    use crate::{
+        app,
+        models::{ClassMember, ClassRole, PendingEnrollment, RosterRow, User},
+        testing::{
+            self,
+            oidc::{self, Identity},
+        },
+    };
+
+    const GOOGLE: &str = "https://accounts.google.com";
+    const SCHOOL: &str = "https://login.school.example";
+
+    #[test]
+    fn email_is_matched_only_when_vouched_for() {
+        assert!(vouches_for_email(Some(true), false));
+        assert!(!vouches_for_email(Some(false), true));
+        assert!(!vouches_for_email(None, false));
+        assert!(vouches_for_email(None, true));
+    }
+
+    #[tokio::test]
+    async fn first_login_creates_the_user_later_ones_refresh_it() {
+        let db = testing::db().await;
+
+        let created = sign_in(&db, None, GOOGLE, "42", "old@example.com", "Old", true)
+            .await
+            .expect("user created");
+        let owner = identity_owner(&db, GOOGLE, "42")
+            .await
+            .expect("owner looked up");
+        assert_eq!(owner, Some(created));
+
+        let refreshed = sign_in(&db, owner, GOOGLE, "42", "new@example.com", "New", true)
+            .await
+            .expect("user refreshed");
+        assert_eq!(refreshed, created);
+
+        let users = User::all(&db).await.expect("users listed");
+        let emails: Vec<_> = users.iter().map(|user| user.email.as_str()).collect();
+        assert_eq!(emails, ["new@example.com"]);
+    }
+
+    #[tokio::test]
+    async fn same_subject_at_another_provider_is_another_login() {
+        let db = testing::db().await;
+
+        sign_in(&db, None, GOOGLE, "42", "a@example.com", "A", true)
+            .await
+            .expect("user created");
+
+        assert_eq!(
+            identity_owner(&db, SCHOOL, "42")
+                .await
+                .expect("owner looked up"),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn linked_logins_sign_in_as_the_same_user() {
+        let db = testing::db().await;
+        let user = sign_in(&db, None, GOOGLE, "42", "a@example.com", "A", true)
+            .await
+            .expect("user created");
+
+        let existing = identity_owner(&db, SCHOOL, "a")
+            .await
+            .expect("owner looked up");
+        link_login(&db, user, existing, SCHOOL, "a")
+            .await
+            .expect("login linked");
+
+        assert_eq!(
+            identity_owner(&db, SCHOOL, "a")
+                .await
+                .expect("owner looked up"),
+            Some(user)
+        );
+
+        // linking again is no harm
+        assert_eq!(link_login(&db, user, Some(user), SCHOOL, "a").await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn login_of_someone_else_is_not_linked() {
+        let db = testing::db().await;
+        let owner = sign_in(&db, None, GOOGLE, "42", "a@example.com", "A", true)
+            .await
+            .expect("user created");
+        let other = sign_in(&db, None, SCHOOL, "b", "b@example.com", "B", true)
+            .await
+            .expect("user created");
+
+        assert_eq!(
+            link_login(&db, other, Some(owner), GOOGLE, "42").await,
+            Err(StatusCode::CONFLICT)
+        );
+        assert_eq!(
+            identity_owner(&db, GOOGLE, "42")
+                .await
+                .expect("owner looked up"),
+            Some(owner)
+        );
+    }
+
+    /// Signs in through the app at a local provider, returns how the callback answered and
+    /// the issuer of the provider.
+    async fn provider_login(
+        db: &SqlitePool,
+        identity: Identity,
+        trust_email: bool,
+    ) -> (StatusCode, String) {
+        let issuer = oidc::issuer(identity).await;
+
+        let provider = OidcProvider {
+            id: "school".to_owned(),
+            label: "School".to_owned(),
+            client: discover(
+                "http://localhost/",
+                issuer.clone(),
+                oidc::CLIENT_ID.to_owned(),
+                "secret".to_owned(),
+            )
+            .await
+            .expect("provider discovered"),
+            scopes: DEFAULT_SCOPES.map(str::to_owned).to_vec(),
+            trust_email,
+        };
+
+        let (router, _) = app(db.clone(), vec![provider], testing::args(&[]))
+            .await
+            .expect("app started");
+
+        let login = router
+            .clone()
+            .oneshot(
+                Request::get("/auth/school")
+                    .body(Body::empty())
+                    .expect("valid request"),
+            )
+            .await
+            .expect("request served");
+
+        let cookie = login
+            .headers()
+            .get(header::SET_COOKIE)
+            .and_then(|cookie| cookie.to_str().ok())
+            .and_then(|cookie| cookie.split(';').next())
+            .expect("session cookie set")
+            .to_owned();
+
+        let authorize = login
+            .headers()
+            .get(header::LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .and_then(|location| reqwest::Url::parse(location).ok())
+            .expect("redirected to the provider");
+        let param = |name: &str| {
+            authorize
+                .query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+                .expect("login parameter")
+        };
+
+        let callback = format!(
+            "/auth/callback?code={}&state={}",
+            param("nonce"),
+            param("state")
+        );
+
+        let response = router
+            .oneshot(
+                Request::get(callback)
+                    .header(header::COOKIE, cookie)
+                    .body(Body::empty())
+                    .expect("valid request"),
+            )
+            .await
+            .expect("request served");
+
+        (response.status(), issuer)
+    }
+
+    /// A class with a student imported under `email`, waiting for them to sign in.
+    async fn imported(db: &SqlitePool, email: &str) -> Uuid {
+        let teacher = testing::user(db, "teacher").await;
+        let class_id = testing::class(db, &teacher).await;
+        let rows = [RosterRow {
+            email: email.to_owned(),
+            name: String::new(),
+            student_id: None,
+        }];
+
+        PendingEnrollment::import(db, class_id, &rows, &[])
+            .await
+            .expect("roster imported");
+
+        class_id
+    }
+
+    #[tokio::test]
+    async fn provider_login_signs_in_and_claims_the_enrollment() {
+        let db = testing::db().await;
+        let class_id = imported(&db, "ada@school.example").await;
+
+        let identity = Identity {
+            subject: "ada",
+            email: "ada@school.example",
+            name: "Ada",
+            email_verified: Some(true),
+        };
+        let (status, issuer) = provider_login(&db, identity, false).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+
+        let user_id = identity_owner(&db, &issuer, "ada")
+            .await
+            .expect("owner looked up")
+            .expect("login linked");
+        let users = User::all(&db).await.expect("users listed");
+        assert!(
+            users
+                .iter()
+                .any(|user| user.id == user_id && user.name == "Ada")
+        );
+
+        assert_eq!(
+            ClassMember::role_of(&db, class_id, user_id)
+                .await
+                .expect("role read"),
+            Some(ClassRole::Student)
+        );
+    }
+
+    #[tokio::test]
+    async fn email_without_a_claim_is_matched_only_when_trusted() {
+        for trust_email in [false, true] {
+            let db = testing::db().await;
+            let class_id = imported(&db, "ada@school.example").await;
+
+            let identity = Identity {
+                subject: "ada",
+                email: "ada@school.example",
+                name: "Ada",
+                email_verified: None,
+            };
+            let (status, issuer) = provider_login(&db, identity, trust_email).await;
+            assert_eq!(status, StatusCode::SEE_OTHER);
+
+            let user_id = identity_owner(&db, &issuer, "ada")
+                .await
+                .expect("owner looked up")
+                .expect("login linked");
+            let role = ClassMember::role_of(&db, class_id, user_id)
+                .await
+                .expect("role read");
+
+            assert_eq!(role.is_some(), trust_email, "trusted: {trust_email}");
+        }
+    }
+}
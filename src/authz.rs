@@ -0,0 +1,260 @@
+//! Who may see what. Every route that takes the id of a class, an assignment or an attempt
+//! asks here before showing anything of it.
+//!
+//! Students see their own attempts and the assignments assigned to them, teachers and
+//! teaching assistants everything of the students in their classes, administrators all.
+
+use std::collections::HashSet;
+
+use reqwest::StatusCode;
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::{auth, models::User};
+
+/// Turns the outcome of a check into the rejection of a route, if there is one.
+pub fn require(allowed: sqlx::Result<bool>) -> Result<(), StatusCode> {
+    if allowed.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? {
+        Ok(())
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+/// Members of the class, and administrators.
+pub async fn can_view_class(db: &SqlitePool, user: &User, class_id: Uuid) -> sqlx::Result<bool> {
+    auth::class_role(db, class_id, user)
+        .await
+        .map(|role| role.is_some())
+}
+
+/// Students it is assigned to, the staff of the classes it is assigned in, and administrators.
+pub async fn can_view_assignment(
+    db: &SqlitePool,
+    user: &User,
+    assignment_id: Uuid,
+) -> sqlx::Result<bool> {
+    if user.is_admin {
+        return Ok(true);
+    }
+
+    sqlx::query_scalar!(
+        r#"SELECT EXISTS(
+            SELECT 1 FROM user_assignments ua
+            LEFT JOIN class_members cm ON cm.class_id = ua.class_id AND cm.user_id = ?2
+            WHERE ua.assignment_id = ?1
+            AND (ua.user_id = ?2 OR cm.role IN ('teacher', 'ta'))
+        ) as "visible!: bool""#,
+        assignment_id,
+        user.id,
+    )
+    .fetch_one(db)
+    .await
+}
+
+/// Only the students the assignment is assigned to submit attempts at it.
+pub async fn can_submit(db: &SqlitePool, user: &User, assignment_id: Uuid) -> sqlx::Result<bool> {
+    sqlx::query_scalar!(
+        r#"SELECT EXISTS(
+            SELECT 1 FROM user_assignments WHERE assignment_id = ? AND user_id = ?
+        ) as "assigned!: bool""#,
+        assignment_id,
+        user.id,
+    )
+    .fetch_one(db)
+    .await
+}
+
+/// Its author, and whoever may manage it.
+pub async fn can_view_attempt(
+    db: &SqlitePool,
+    user: &User,
+    attempt_id: Uuid,
+) -> sqlx::Result<bool> {
+    let is_author = sqlx::query_scalar!(
+        r#"SELECT EXISTS(
+            SELECT 1 FROM attempts WHERE id = ? AND user_id = ?
+        ) as "is_author!: bool""#,
+        attempt_id,
+        user.id,
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(is_author || can_manage_attempt(db, user, attempt_id).await?)
+}
+
+/// The staff of a class the author of the attempt does the assignment in, and administrators.
+///
+/// They cancel and rejudge its evaluation.
+pub async fn can_manage_attempt(
+    db: &SqlitePool,
+    user: &User,
+    attempt_id: Uuid,
+) -> sqlx::Result<bool> {
+    if user.is_admin {
+        return Ok(true);
+    }
+
+    sqlx::query_scalar!(
+        r#"SELECT EXISTS(
+            SELECT 1 FROM attempts a
+            JOIN user_assignments ua ON ua.assignment_id = a.assignment_id AND ua.user_id = a.user_id
+            JOIN class_members cm ON cm.class_id = ua.class_id
+            WHERE a.id = ? AND cm.user_id = ? AND cm.role IN ('teacher', 'ta')
+        ) as "is_staff!: bool""#,
+        attempt_id,
+        user.id,
+    )
+    .fetch_one(db)
+    .await
+}
+
+/// Authors whose attempts at the assignment the user may see, `None` if everyone's.
+///
+/// Agrees with [`can_view_attempt`], for listing many attempts at once.
+pub async fn visible_authors(
+    db: &SqlitePool,
+    user: &User,
+    assignment_id: Uuid,
+) -> sqlx::Result<Option<HashSet<Uuid>>> {
+    if user.is_admin {
+        return Ok(None);
+    }
+
+    let mut authors: HashSet<Uuid> = sqlx::query_scalar!(
+        r#"SELECT ua.user_id as "user_id: Uuid" FROM user_assignments ua
+        JOIN class_members cm ON cm.class_id = ua.class_id
+        WHERE ua.assignment_id = ? AND cm.user_id = ? AND cm.role IN ('teacher', 'ta')"#,
+        assignment_id,
+        user.id,
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .collect();
+
+    authors.insert(user.id);
+
+    Ok(Some(authors))
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::{
+        can_manage_attempt, can_submit, can_view_assignment, can_view_attempt, can_view_class,
+        visible_authors,
+    };
+    use crate::{
+        models::{ClassRole, User},
+        testing,
+    };
+
+    /// What a user may do with the class, its assignment and the student's attempt at it.
+    async fn permissions(
+        db: &sqlx::SqlitePool,
+        user: &User,
+        class_id: Uuid,
+        assignment_id: Uuid,
+        attempt_id: Uuid,
+    ) -> [bool; 5] {
+        [
+            can_view_class(db, user, class_id).await,
+            can_view_assignment(db, user, assignment_id).await,
+            can_submit(db, user, assignment_id).await,
+            can_view_attempt(db, user, attempt_id).await,
+            can_manage_attempt(db, user, attempt_id).await,
+        ]
+        .map(|allowed| allowed.expect("check ran"))
+    }
+
+    #[tokio::test]
+    async fn roles_see_what_they_should() {
+        let db = testing::db().await;
+        let admin = testing::admin(&db, "admin").await;
+        let teacher = testing::user(&db, "teacher").await;
+        let ta = testing::user(&db, "ta").await;
+        let student = testing::user(&db, "student").await;
+        let classmate = testing::user(&db, "classmate").await;
+        let outsider = testing::user(&db, "outsider").await;
+
+        let class_id = testing::class(&db, &teacher).await;
+        testing::member(&db, class_id, &ta, ClassRole::Ta).await;
+        let assignment_id = testing::assignment(&db).await;
+        testing::assign(&db, class_id, assignment_id, &student).await;
+        testing::assign(&db, class_id, assignment_id, &classmate).await;
+        let attempt = testing::attempt(&db, assignment_id, &student).await;
+
+        // class, assignment, submit, view attempt, manage attempt
+        let expected = [
+            (&admin, [true, true, false, true, true]),
+            (&teacher, [true, true, false, true, true]),
+            (&ta, [true, true, false, true, true]),
+            (&student, [true, true, true, true, false]),
+            (&classmate, [true, true, true, false, false]),
+            (&outsider, [false, false, false, false, false]),
+        ];
+
+        for (user, allowed) in expected {
+            assert_eq!(
+                permissions(&db, user, class_id, assignment_id, attempt.id).await,
+                allowed,
+                "{}",
+                user.name,
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn staff_of_another_class_see_nothing() {
+        let db = testing::db().await;
+        let teacher = testing::user(&db, "teacher").await;
+        let other_teacher = testing::user(&db, "other teacher").await;
+        let student = testing::user(&db, "student").await;
+
+        let class_id = testing::class(&db, &teacher).await;
+        testing::class(&db, &other_teacher).await;
+        let assignment_id = testing::assignment(&db).await;
+        testing::assign(&db, class_id, assignment_id, &student).await;
+        let attempt = testing::attempt(&db, assignment_id, &student).await;
+
+        assert_eq!(
+            permissions(&db, &other_teacher, class_id, assignment_id, attempt.id).await,
+            [false; 5],
+        );
+    }
+
+    #[tokio::test]
+    async fn listings_agree_with_attempt_checks() {
+        let db = testing::db().await;
+        let admin = testing::admin(&db, "admin").await;
+        let teacher = testing::user(&db, "teacher").await;
+        let student = testing::user(&db, "student").await;
+        let classmate = testing::user(&db, "classmate").await;
+
+        let class_id = testing::class(&db, &teacher).await;
+        let assignment_id = testing::assignment(&db).await;
+        testing::assign(&db, class_id, assignment_id, &student).await;
+        testing::assign(&db, class_id, assignment_id, &classmate).await;
+
+        let authors = async |user| {
+            visible_authors(&db, user, assignment_id)
+                .await
+                .expect("authors listed")
+                .map(|authors| {
+                    let mut authors: Vec<Uuid> = authors.into_iter().collect();
+                    authors.sort();
+                    authors
+                })
+        };
+
+        let mut class = vec![teacher.id, student.id, classmate.id];
+        class.sort();
+
+        assert_eq!(authors(&admin).await, None);
+        assert_eq!(authors(&teacher).await, Some(class));
+        assert_eq!(authors(&student).await, Some(vec![student.id]));
+    }
+}
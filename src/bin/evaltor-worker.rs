@@ -0,0 +1,38 @@
+#![deny(
+    clippy::expect_used,
+    clippy::future_not_send,
+    clippy::pedantic,
+    clippy::as_conversions,
+    clippy::unwrap_used,
+    unsafe_code
+)]
+#![allow(
+    clippy::module_name_repetitions,
+    clippy::missing_errors_doc,
+    clippy::manual_non_exhaustive,
+    clippy::multiple_crate_versions
+)]
+
+use std::io;
+
+use clap::Parser;
+use evaltor::{WorkerArgs, run_worker};
+use tokio::signal::unix::{SignalKind, signal};
+
+#[tokio::main]
+async fn main() -> Result<(), io::Error> {
+    _ = dotenvy::dotenv();
+
+    let args = WorkerArgs::parse();
+
+    let mut terminate = signal(SignalKind::terminate())?;
+
+    let shutdown = async move {
+        tokio::select! {
+            _ = terminate.recv() => {}
+            _ = tokio::signal::ctrl_c() => {}
+        }
+    };
+
+    run_worker(args, shutdown).await
+}
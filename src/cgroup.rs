@@ -0,0 +1,92 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use uuid::Uuid;
+
+/// Resources a single sandboxed run actually consumed.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    pub wall_time: Duration,
+    pub cpu_time: Option<Duration>,
+    pub peak_memory_kb: Option<i64>,
+    pub oom_killed: bool,
+}
+
+impl ResourceUsage {
+    pub fn wall_time_ms(&self) -> Option<i64> {
+        i64::try_from(self.wall_time.as_millis()).ok()
+    }
+
+    pub fn cpu_time_ms(&self) -> Option<i64> {
+        self.cpu_time
+            .and_then(|cpu_time| i64::try_from(cpu_time.as_millis()).ok())
+    }
+}
+
+/// A cgroup v2 directory created for one run and handed to nsjail as its `--cgroupv2_mount`.
+///
+/// nsjail creates its own child cgroup below it and removes it when the jail exits. The
+/// counters of this parent are hierarchical, so they still hold the usage of the whole jail
+/// afterwards.
+pub struct RunCgroup {
+    path: PathBuf,
+}
+
+impl RunCgroup {
+    pub async fn create(root: &Path, run_id: Uuid) -> io::Result<Self> {
+        let path = root.join(run_id.to_string());
+
+        fs::create_dir(&path).await?;
+        fs::write(path.join("cgroup.subtree_control"), "+memory +pids +cpu").await?;
+
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reads the counters and removes the cgroup. Counters the kernel does not provide are
+    /// left empty.
+    pub async fn finish(self, wall_time: Duration) -> ResourceUsage {
+        let cpu_time = fs::read_to_string(self.path.join("cpu.stat"))
+            .await
+            .ok()
+            .and_then(|stat| read_key(&stat, "usage_usec"))
+            .map(Duration::from_micros);
+
+        let peak_memory_kb = fs::read_to_string(self.path.join("memory.peak"))
+            .await
+            .ok()
+            .and_then(|peak| peak.trim().parse::<i64>().ok())
+            .map(|bytes| bytes / 1024);
+
+        let oom_killed = fs::read_to_string(self.path.join("memory.events"))
+            .await
+            .ok()
+            .and_then(|events| read_key(&events, "oom_kill"))
+            .is_some_and(|kills| kills > 0);
+
+        if let Err(err) = fs::remove_dir(&self.path).await {
+            eprintln!("Failed to remove cgroup {}: {err:?}", self.path.display());
+        }
+
+        ResourceUsage {
+            wall_time,
+            cpu_time,
+            peak_memory_kb,
+            oom_killed,
+        }
+    }
+}
+
+/// Reads a value out of a flat-keyed cgroup file such as `cpu.stat`.
+fn read_key(contents: &str, key: &str) -> Option<u64> {
+    contents.lines().find_map(|line| {
+        let (name, value) = line.split_once(' ')?;
+        (name == key).then(|| value.trim().parse().ok()).flatten()
+    })
+}
@@ -0,0 +1,147 @@
+use std::path::{Path, PathBuf};
+
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::{
+    EvaltorArgs,
+    models::{Attempt, Compilation, Language, Limits, NetworkPolicy, Runner, Test},
+    nsjail::{BuildMount, NSJailBlueprint},
+};
+
+/// Directory an attempt's source is stored in, mounted as the jail's `/workspace`.
+pub struct AttemptDir {
+    pub workspace: PathBuf,
+}
+
+impl AttemptDir {
+    pub fn new(submissions: &Path, attempt: &Attempt) -> Self {
+        Self {
+            workspace: submissions
+                .join(attempt.assignment_id.to_string())
+                .join(attempt.user_id.to_string())
+                .join(attempt.id.to_string()),
+        }
+    }
+
+    /// Output of the compile step, mounted as `/workspace/build`.
+    pub fn build(&self) -> PathBuf {
+        self.workspace.join("build")
+    }
+}
+
+/// Blueprint of the compile step, `None` if the language is interpreted.
+pub fn compile_blueprint(
+    config: &EvaltorArgs,
+    language: &Language,
+    dir: &AttemptDir,
+) -> Option<NSJailBlueprint> {
+    let command = language.compile_command.as_ref()?;
+
+    Some(NSJailBlueprint {
+        tests: config.tests.clone(),
+        memory_limit: language.compile_memory_limit,
+        time_limit: language.compile_time_limit,
+        max_cpus: 1,
+        network: NetworkPolicy::Isolated,
+        rootfs: config.sandbox.rootfs.join(&language.rootfs),
+        mountpoint: dir.workspace.clone(),
+        build_dir: Some(BuildMount::Writable(dir.build())),
+        command: command.clone(),
+        write_stdin: false,
+        cpu: None,
+    })
+}
+
+pub fn test_blueprint(
+    config: &EvaltorArgs,
+    language: &Language,
+    limits: Limits,
+    dir: &AttemptDir,
+) -> NSJailBlueprint {
+    NSJailBlueprint {
+        tests: config.tests.clone(),
+        memory_limit: limits.memory_limit,
+        time_limit: limits.time_limit,
+        max_cpus: limits.max_cpus,
+        network: limits.network_policy,
+        rootfs: config.sandbox.rootfs.join(&language.rootfs),
+        mountpoint: dir.workspace.clone(),
+        build_dir: Some(BuildMount::ReadOnly(dir.build())),
+        command: language.run_command.clone(),
+        write_stdin: true,
+        cpu: None,
+    }
+}
+
+/// Blueprint of a queued compilation, rebuilt when a judge worker claims it.
+pub async fn compilation_blueprint(
+    db: &SqlitePool,
+    config: &EvaltorArgs,
+    compilation: &Compilation,
+) -> sqlx::Result<Option<NSJailBlueprint>> {
+    let attempt = Attempt::by_id(db, compilation.attempt_id).await?;
+    let language = Language::for_attempt(db, attempt.id).await?;
+
+    Ok(compile_blueprint(
+        config,
+        &language,
+        &AttemptDir::new(&config.submissions, &attempt),
+    ))
+}
+
+/// Blueprint of a queued runner, rebuilt when a judge worker claims it.
+///
+/// The limits recorded on the runner when it was queued are used, not the current ones.
+pub async fn runner_blueprint(
+    db: &SqlitePool,
+    config: &EvaltorArgs,
+    runner: &Runner,
+) -> sqlx::Result<NSJailBlueprint> {
+    let attempt = Attempt::by_id(db, runner.attempt_id).await?;
+    let language = Language::for_attempt(db, attempt.id).await?;
+
+    let limits = Limits {
+        memory_limit: runner.memory_limit,
+        time_limit: runner.time_limit,
+        max_cpus: runner.max_cpus,
+        network_policy: runner.network_policy,
+    };
+
+    Ok(test_blueprint(
+        config,
+        &language,
+        limits,
+        &AttemptDir::new(&config.submissions, &attempt),
+    ))
+}
+
+/// Blueprints of everything that has to run to evaluate an attempt.
+pub struct AttemptPlan {
+    pub compile: Option<NSJailBlueprint>,
+    pub tests: Vec<(Uuid, NSJailBlueprint)>,
+}
+
+impl AttemptPlan {
+    pub async fn new(
+        db: &SqlitePool,
+        config: &EvaltorArgs,
+        attempt: &Attempt,
+        language: &Language,
+    ) -> sqlx::Result<Self> {
+        let dir = AttemptDir::new(&config.submissions, attempt);
+
+        let mut tests = Vec::new();
+
+        for test in Test::for_assignment(db, attempt.assignment_id).await? {
+            let limits = Limits::for_test(db, test.id, language.id).await?;
+
+            tests.push((test.id, test_blueprint(config, language, limits, &dir)));
+        }
+
+        Ok(Self {
+            compile: compile_blueprint(config, language, &dir),
+            tests,
+        })
+    }
+}
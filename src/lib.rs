@@ -21,7 +21,7 @@
     clippy::multiple_crate_versions
 )]
 
-use std::io;
+use std::{io, str::FromStr};
 
 use askama::Template;
 use axum::{
@@ -32,63 +32,110 @@ use axum::{
     routing::get,
 };
 
-use sqlx::SqlitePool;
+use sqlx::{ConnectOptions, Connection, SqlitePool, sqlite::SqliteConnectOptions};
 use tokio::fs;
 use tower_sessions::{Expiry, SessionManagerLayer, cookie::time::Duration};
 use tower_sessions_sqlx_store::SqliteStore;
 use uuid::Uuid;
 
 use crate::{
-    models::{Assignment, Class, Test, TestType},
+    models::{Assignment, Class, ClassMember, ClassRole, Test, TestType},
     runner_manager::RunnerManager,
     state::EvaltorState,
 };
 
 pub use args::EvaltorArgs;
 pub use points::Points;
+pub use worker::{WorkerArgs, run_worker};
 
 mod args;
 mod auth;
+mod authz;
+mod cgroup;
+mod evaluation;
 pub mod filters;
 mod models;
 mod nsjail;
 mod points;
 mod routes;
 mod runner_manager;
+mod sandbox;
 mod state;
 mod templates;
+#[cfg(test)]
+mod testing;
+mod worker;
+
+/// Stops the judging of a [`server`] once the process is asked to exit.
+#[derive(Clone, Debug)]
+pub struct Shutdown {
+    runner_manager: RunnerManager,
+}
 
-pub async fn server(args: EvaltorArgs) -> Result<Router, io::Error> {
+impl Shutdown {
+    /// Rejects new submissions and waits for the running evaluations, see `--shutdown-timeout`.
+    pub async fn run(&self) {
+        self.runner_manager.shutdown().await;
+    }
+}
+
+pub async fn server(args: EvaltorArgs) -> Result<(Router, Shutdown), io::Error> {
     let db_pool = SqlitePool::connect("sqlite:data.db")
         .await
         .map_err(io::Error::other)?;
 
+    // migrations rebuild tables, which must not cascade to the rows referencing them
+    let mut migration_conn = SqliteConnectOptions::from_str("sqlite:data.db")
+        .map_err(io::Error::other)?
+        .foreign_keys(false)
+        .connect()
+        .await
+        .map_err(io::Error::other)?;
+
     sqlx::migrate!("./migrations")
-        .run(&db_pool)
+        .run(&mut migration_conn)
         .await
         .map_err(io::Error::other)?;
 
+    migration_conn.close().await.map_err(io::Error::other)?;
+
     // make_test_data(&db_pool, &args).await;
 
-    let oidc_client = auth::build_oidc_client(
-        args.hostname.clone(),
-        args.google_client_id.clone(),
-        args.google_client_secret.clone(),
-    )
-    .await?;
+    let oidc_providers = auth::build_oidc_providers(&args).await?;
+
+    if args.dev_auth {
+        eprintln!("Development login is on, anyone can sign in as anyone");
+    }
+
+    app(db_pool, oidc_providers, args).await
+}
 
+/// Routes of the server on the migrated database, with its judge workers started.
+async fn app(
+    db_pool: SqlitePool,
+    oidc_providers: Vec<auth::OidcProvider>,
+    args: EvaltorArgs,
+) -> Result<(Router, Shutdown), io::Error> {
     let session_store = SqliteStore::new(db_pool.clone());
     session_store.migrate().await.map_err(io::Error::other)?;
     let session_layer = SessionManagerLayer::new(session_store)
         .with_same_site(tower_sessions::cookie::SameSite::Lax)
         .with_expiry(Expiry::OnInactivity(Duration::days(7)));
 
-    let runner_manager = RunnerManager::new(db_pool.clone());
+    let sandbox = args.sandbox.build();
+
+    let runner_manager = RunnerManager::start(db_pool.clone(), sandbox, args.clone())
+        .await
+        .map_err(io::Error::other)?;
+
+    let shutdown = Shutdown {
+        runner_manager: runner_manager.clone(),
+    };
 
     let state = EvaltorState {
         db_pool,
         runner_manager,
-        oidc_client,
+        oidc_providers: oidc_providers.into(),
         config: args,
     };
 
@@ -97,11 +144,12 @@ pub async fn server(args: EvaltorArgs) -> Result<Router, io::Error> {
         .merge(routes::class::router())
         .merge(routes::assignment::router())
         .merge(routes::attempt::router())
+        .merge(routes::worker::router())
         .merge(auth::auth_router())
         .layer(session_layer)
         .with_state(state);
 
-    Ok(router)
+    Ok((router, shutdown))
 }
 
 #[derive(Debug, Template)]
@@ -110,24 +158,20 @@ struct IndexPage {
     user_name: String,
     user_email: String,
     classes: Vec<Class>,
+    /// Ids and labels of the providers another login can be linked from
+    providers: Vec<(String, String)>,
 }
 
 async fn index(auth: auth::AuthUser, State(state): State<EvaltorState>) -> impl IntoResponse {
-    let classes = sqlx::query_as!(
-        Class,
-        r#"SELECT id as "id: uuid::Uuid", creator_id as "creator_id: uuid::Uuid", name, description FROM classes"#
-    )
-    .fetch_all(&state.db_pool)
-    .await
-    .map_err(|e| {
-        dbg!(e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    let classes = Class::for_member(&state.db_pool, &auth)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     IndexPage {
         user_name: auth.name.clone(),
         user_email: auth.email.clone(),
         classes,
+        providers: auth::providers(&state),
     }
     .render()
     .map(Html)
@@ -159,6 +203,10 @@ async fn make_test_data(db: &SqlitePool, args: &EvaltorArgs) {
     .await
     .expect("Failed to insert test class");
 
+    ClassMember::add(db, class.id, user_id, ClassRole::Teacher)
+        .await
+        .expect("Failed to add test teacher");
+
     let aid = Uuid::parse_str("949807b4-226b-4803-8058-c751c930220e").unwrap();
     let assignment = Assignment {
         id: aid,
@@ -181,6 +229,8 @@ async fn make_test_data(db: &SqlitePool, args: &EvaltorArgs) {
     .await
     .expect("Failed to insert test assignment");
 
+    // new assignments are Python-only, others are enabled in assignment_languages
+
     // ---
 
     let t1id = uuid::Uuid::new_v4();
@@ -247,3 +297,93 @@ async fn make_test_data(db: &SqlitePool, args: &EvaltorArgs) {
         .await
         .expect("cannot create test 2 dir");
 }
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        Router,
+        body::{self, Body},
+        http::{Request, StatusCode, header},
+        response::Response,
+    };
+    use sqlx::SqlitePool;
+    use tower::ServiceExt;
+
+    use crate::{app, models::User, testing};
+
+    /// The app on its own in-memory database, judging nothing.
+    async fn router(db: &SqlitePool, dev_auth: bool) -> Router {
+        let args = testing::args(if dev_auth { &["--dev-auth"] } else { &[] });
+
+        app(db.clone(), Vec::new(), args)
+            .await
+            .expect("app started")
+            .0
+    }
+
+    async fn dev_login(router: &Router, form: &str) -> Response {
+        let request = Request::post("/auth/dev")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from(form.to_owned()))
+            .expect("valid request");
+
+        router
+            .clone()
+            .oneshot(request)
+            .await
+            .expect("request served")
+    }
+
+    #[tokio::test]
+    async fn dev_login_is_off_by_default() {
+        let db = testing::db().await;
+        let router = router(&db, false).await;
+
+        let response = dev_login(&router, "email=a%40example.com&role=admin").await;
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(User::all(&db).await.expect("users listed").is_empty());
+    }
+
+    #[tokio::test]
+    async fn dev_login_creates_the_user_once_and_signs_in() {
+        let db = testing::db().await;
+        let router = router(&db, true).await;
+
+        let first = dev_login(&router, "email=a%40example.com&name=Ada&role=student").await;
+        assert_eq!(first.status(), StatusCode::SEE_OTHER);
+
+        let second = dev_login(&router, "email=a%40example.com&name=Other&role=admin").await;
+        assert_eq!(second.status(), StatusCode::SEE_OTHER);
+
+        let users = User::all(&db).await.expect("users listed");
+        let signed_up: Vec<_> = users
+            .iter()
+            .map(|user| (user.name.as_str(), user.is_admin))
+            .collect();
+        assert_eq!(signed_up, [("Ada", true)]);
+
+        let cookie = second
+            .headers()
+            .get(header::SET_COOKIE)
+            .and_then(|cookie| cookie.to_str().ok())
+            .and_then(|cookie| cookie.split(';').next())
+            .expect("session cookie set");
+
+        let index = router
+            .oneshot(
+                Request::get("/")
+                    .header(header::COOKIE, cookie)
+                    .body(Body::empty())
+                    .expect("valid request"),
+            )
+            .await
+            .expect("request served");
+        assert_eq!(index.status(), StatusCode::OK);
+
+        let page = body::to_bytes(index.into_body(), usize::MAX)
+            .await
+            .expect("page read");
+        assert!(String::from_utf8_lossy(&page).contains("Ada (a@example.com)"));
+    }
+}
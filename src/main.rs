@@ -13,11 +13,13 @@
     clippy::multiple_crate_versions
 )]
 
+use std::future::IntoFuture;
 use std::io;
 
 use clap::Parser;
 use evaltor::{EvaltorArgs, server};
 use tokio::net::TcpListener;
+use tokio::signal::unix::{SignalKind, signal};
 
 #[tokio::main]
 async fn main() -> Result<(), io::Error> {
@@ -29,7 +31,24 @@ async fn main() -> Result<(), io::Error> {
         .await
         .map_err(io::Error::other)?;
 
-    let app = server(args).await?;
+    let (app, shutdown) = server(args).await?;
 
-    axum::serve(listener, app).await
+    let mut terminate = signal(SignalKind::terminate())?;
+
+    let serve = axum::serve(listener, app).into_future();
+    tokio::pin!(serve);
+
+    tokio::select! {
+        result = &mut serve => return result,
+        _ = terminate.recv() => {}
+        result = tokio::signal::ctrl_c() => result?,
+    }
+
+    eprintln!("Shutting down, new submissions are rejected");
+
+    // pages and remote workers are still served while the running evaluations finish
+    tokio::select! {
+        result = &mut serve => result,
+        () = shutdown.run() => Ok(()),
+    }
 }
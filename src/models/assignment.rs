@@ -24,4 +24,23 @@ impl Assignment {
         .fetch_all(db)
         .await
     }
+
+    /// Assignments given to at least one student of the class.
+    pub async fn for_class(db: &SqlitePool, class_id: Uuid) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Assignment,
+            r#"SELECT DISTINCT
+            a.id as "id: Uuid",
+            a.name,
+            a.description
+            FROM assignments a
+            JOIN user_assignments ua ON ua.assignment_id = a.id
+            WHERE ua.class_id = ?
+            ORDER BY a.name
+            "#,
+            class_id
+        )
+        .fetch_all(db)
+        .await
+    }
 }
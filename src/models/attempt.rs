@@ -1,13 +1,145 @@
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
 use uuid::Uuid;
 
+use crate::models::SubmissionQuota;
+
 #[derive(Serialize, Deserialize, Debug, sqlx::FromRow)]
 pub struct Attempt {
     pub id: Uuid,
 
     pub assignment_id: Uuid,
     pub user_id: Uuid,
+    pub language_id: Uuid,
 
     pub submitted_at: NaiveDateTime,
 }
+
+impl Attempt {
+    pub async fn by_id(db: &SqlitePool, id: Uuid) -> sqlx::Result<Self> {
+        sqlx::query_as!(
+            Attempt,
+            r#"SELECT
+                id as "id: uuid::Uuid",
+                assignment_id as "assignment_id: uuid::Uuid",
+                user_id as "user_id: uuid::Uuid",
+                language_id as "language_id: uuid::Uuid",
+                submitted_at as "submitted_at: chrono::NaiveDateTime"
+            FROM attempts WHERE id = ?"#,
+            id
+        )
+        .fetch_one(db)
+        .await
+    }
+
+    /// Attempts at the assignment by the students it is assigned to in the class.
+    pub async fn for_class_assignment(
+        db: &SqlitePool,
+        class_id: Uuid,
+        assignment_id: Uuid,
+    ) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Attempt,
+            r#"SELECT
+                a.id as "id: uuid::Uuid",
+                a.assignment_id as "assignment_id: uuid::Uuid",
+                a.user_id as "user_id: uuid::Uuid",
+                a.language_id as "language_id: uuid::Uuid",
+                a.submitted_at as "submitted_at: chrono::NaiveDateTime"
+            FROM attempts a
+            JOIN user_assignments ua ON ua.assignment_id = a.assignment_id AND ua.user_id = a.user_id
+            WHERE ua.class_id = ? AND a.assignment_id = ?"#,
+            class_id,
+            assignment_id
+        )
+        .fetch_all(db)
+        .await
+    }
+
+    /// Inserts the attempt unless it breaks the submission limits of its assignment, returns
+    /// why it does instead.
+    ///
+    /// The limits are checked within the same write transaction, so parallel submissions
+    /// cannot all slip through the last free slot.
+    pub async fn insert_within_quota(&self, db: &SqlitePool) -> sqlx::Result<Result<(), String>> {
+        let mut tx = db.begin_with("BEGIN IMMEDIATE").await?;
+
+        let quota = SubmissionQuota::for_user(
+            &mut *tx,
+            self.assignment_id,
+            self.user_id,
+            self.submitted_at,
+        )
+        .await?;
+
+        if let Err(rejection) = quota.check(self.submitted_at) {
+            return Ok(Err(rejection));
+        }
+
+        sqlx::query!(
+            "INSERT INTO attempts (id, assignment_id, user_id, language_id, submitted_at) VALUES (?, ?, ?, ?, ?)",
+            self.id,
+            self.assignment_id,
+            self.user_id,
+            self.language_id,
+            self.submitted_at,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    use super::Attempt;
+    use crate::testing;
+
+    #[tokio::test]
+    async fn last_free_attempt_is_taken_once() {
+        let db = testing::db().await;
+        let student = testing::user(&db, "student").await;
+        let assignment_id = testing::assignment(&db).await;
+
+        sqlx::query!(
+            "UPDATE assignments SET max_attempts = 1 WHERE id = ?",
+            assignment_id
+        )
+        .execute(&db)
+        .await
+        .expect("cap set");
+
+        let attempt = || Attempt {
+            id: Uuid::new_v4(),
+            assignment_id,
+            user_id: student.id,
+            language_id: testing::PYTHON,
+            submitted_at: Utc::now().naive_utc(),
+        };
+
+        let (first, second) = (attempt(), attempt());
+
+        let (first, second) = tokio::join!(
+            first.insert_within_quota(&db),
+            second.insert_within_quota(&db),
+        );
+
+        let count = sqlx::query_scalar!("SELECT count(*) FROM attempts")
+            .fetch_one(&db)
+            .await
+            .expect("attempts counted");
+
+        assert_eq!(count, 1);
+        assert!(matches!(
+            (first, second),
+            (Ok(Ok(())), Ok(Err(_))) | (Ok(Err(_)), Ok(Ok(())))
+        ));
+    }
+}
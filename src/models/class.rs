@@ -2,7 +2,10 @@ use serde::{Deserialize, Serialize};
 use sqlx::SqlitePool;
 use uuid::Uuid;
 
-use crate::Points;
+use crate::{Points, models::User};
+
+/// Hex digits in a join code, as many as the codes generated by the migration have.
+const JOIN_CODE_LENGTH: usize = 8;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Class {
@@ -15,6 +18,97 @@ pub struct Class {
 }
 
 impl Class {
+    pub async fn all(db: &SqlitePool) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Class,
+            r#"SELECT id as "id: uuid::Uuid", creator_id as "creator_id: uuid::Uuid", name, description FROM classes ORDER BY name"#
+        )
+        .fetch_all(db)
+        .await
+    }
+
+    /// Classes the user is a member of, every class for administrators.
+    pub async fn for_member(db: &SqlitePool, user: &User) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Class,
+            r#"SELECT
+                c.id as "id: uuid::Uuid",
+                c.creator_id as "creator_id: uuid::Uuid",
+                c.name,
+                c.description
+            FROM classes c
+            WHERE ?1 OR EXISTS(
+                SELECT 1 FROM class_members cm WHERE cm.class_id = c.id AND cm.user_id = ?2
+            )
+            ORDER BY c.name"#,
+            user.is_admin,
+            user.id,
+        )
+        .fetch_all(db)
+        .await
+    }
+
+    /// Classes the user teaches, every class for administrators.
+    pub async fn taught_by(db: &SqlitePool, user: &User) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Class,
+            r#"SELECT
+                c.id as "id: uuid::Uuid",
+                c.creator_id as "creator_id: uuid::Uuid",
+                c.name,
+                c.description
+            FROM classes c
+            WHERE ?1 OR EXISTS(
+                SELECT 1 FROM class_members cm
+                WHERE cm.class_id = c.id AND cm.user_id = ?2 AND cm.role = 'teacher'
+            )
+            ORDER BY c.name"#,
+            user.is_admin,
+            user.id,
+        )
+        .fetch_all(db)
+        .await
+    }
+
+    /// Class students join with the code.
+    pub async fn by_join_code(db: &SqlitePool, code: &str) -> sqlx::Result<Option<Uuid>> {
+        let code = code.trim().to_uppercase();
+
+        sqlx::query_scalar!(
+            r#"SELECT id as "id: Uuid" FROM classes WHERE join_code = ?"#,
+            code
+        )
+        .fetch_optional(db)
+        .await
+    }
+
+    pub async fn join_code(db: &SqlitePool, class_id: Uuid) -> sqlx::Result<Option<String>> {
+        sqlx::query_scalar!("SELECT join_code FROM classes WHERE id = ?", class_id)
+            .fetch_one(db)
+            .await
+    }
+
+    /// Replaces the join code of the class, the old one stops working.
+    pub async fn regenerate_join_code(db: &SqlitePool, class_id: Uuid) -> sqlx::Result<String> {
+        let code: String = Uuid::new_v4()
+            .simple()
+            .to_string()
+            .chars()
+            .take(JOIN_CODE_LENGTH)
+            .collect::<String>()
+            .to_uppercase();
+
+        sqlx::query!(
+            "UPDATE classes SET join_code = ? WHERE id = ?",
+            code,
+            class_id
+        )
+        .execute(db)
+        .await?;
+
+        Ok(code)
+    }
+
     pub async fn points_for_student(
         db: &SqlitePool,
         class_id: Uuid,
@@ -30,7 +124,7 @@ impl Class {
                     ), 0) AS "maximum!: i64",
                     COALESCE((
                         SELECT SUM(r.points)
-                        FROM runners r
+                        FROM scored_runners r
                         JOIN attempts a ON r.attempt_id = a.id AND a.user_id = ?2
                         JOIN user_assignments ua ON ua.assignment_id = a.assignment_id
                             AND ua.class_id = ?1
@@ -51,3 +145,54 @@ impl Class {
         Ok(Points::new(row.maximum, row.achieved))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Class;
+    use crate::testing;
+
+    #[tokio::test]
+    async fn join_codes_ignore_case_and_whitespace() {
+        let db = testing::db().await;
+        let teacher = testing::user(&db, "teacher").await;
+        let class_id = testing::class(&db, &teacher).await;
+
+        let code = Class::regenerate_join_code(&db, class_id)
+            .await
+            .expect("code generated");
+        let typed = format!("  {}\n", code.to_lowercase());
+
+        assert_eq!(
+            Class::by_join_code(&db, &typed).await.expect("class found"),
+            Some(class_id)
+        );
+    }
+
+    #[tokio::test]
+    async fn regenerated_code_replaces_the_old_one() {
+        let db = testing::db().await;
+        let teacher = testing::user(&db, "teacher").await;
+        let class_id = testing::class(&db, &teacher).await;
+
+        let old = Class::regenerate_join_code(&db, class_id)
+            .await
+            .expect("code generated");
+        let new = Class::regenerate_join_code(&db, class_id)
+            .await
+            .expect("code generated");
+
+        assert_ne!(old, new);
+        assert_eq!(
+            Class::join_code(&db, class_id).await.expect("code read"),
+            Some(new.clone())
+        );
+        assert_eq!(
+            Class::by_join_code(&db, &old).await.expect("lookup ran"),
+            None
+        );
+        assert_eq!(
+            Class::by_join_code(&db, &new).await.expect("lookup ran"),
+            Some(class_id)
+        );
+    }
+}
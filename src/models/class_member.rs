@@ -0,0 +1,286 @@
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::models::User;
+
+/// What a member may do in a class, ordered from the least to the most privileged.
+#[derive(
+    sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord,
+)]
+#[sqlx(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ClassRole {
+    /// Submits attempts and sees their own results
+    Student,
+    /// Watches over evaluations, may cancel and rejudge attempts
+    Ta,
+    /// Manages the class, its assignments and members
+    Teacher,
+}
+
+impl ClassRole {
+    #[must_use]
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Student => "Student",
+            Self::Ta => "Teaching assistant",
+            Self::Teacher => "Teacher",
+        }
+    }
+
+    /// Teachers and teaching assistants.
+    #[must_use]
+    pub fn is_staff(self) -> bool {
+        self >= Self::Ta
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ClassMember {
+    pub class_id: Uuid,
+    pub user_id: Uuid,
+    pub role: ClassRole,
+}
+
+impl ClassMember {
+    pub async fn role_of(
+        db: &SqlitePool,
+        class_id: Uuid,
+        user_id: Uuid,
+    ) -> sqlx::Result<Option<ClassRole>> {
+        sqlx::query_scalar!(
+            r#"SELECT role as "role: ClassRole" FROM class_members WHERE class_id = ? AND user_id = ?"#,
+            class_id,
+            user_id,
+        )
+        .fetch_optional(db)
+        .await
+    }
+
+    /// Adds the user to the class with `role`, members keep the role they already have.
+    pub async fn add(
+        db: &SqlitePool,
+        class_id: Uuid,
+        user_id: Uuid,
+        role: ClassRole,
+    ) -> sqlx::Result<()> {
+        sqlx::query!(
+            "INSERT INTO class_members (class_id, user_id, role) VALUES (?, ?, ?) ON CONFLICT DO NOTHING",
+            class_id,
+            user_id,
+            role,
+        )
+        .execute(db)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Gives the user `role` in the class, whatever role they had.
+    pub async fn set_role(
+        db: &SqlitePool,
+        class_id: Uuid,
+        user_id: Uuid,
+        role: ClassRole,
+    ) -> sqlx::Result<()> {
+        sqlx::query!(
+            "INSERT INTO class_members (class_id, user_id, role) VALUES (?, ?, ?)
+            ON CONFLICT (class_id, user_id) DO UPDATE SET role = excluded.role",
+            class_id,
+            user_id,
+            role,
+        )
+        .execute(db)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Students of the class, by name.
+    pub async fn students(db: &SqlitePool, class_id: Uuid) -> sqlx::Result<Vec<User>> {
+        sqlx::query_as!(
+            User,
+            r#"SELECT
+            u.id as "id: Uuid",
+            u.email,
+            u.name,
+            u.is_admin,
+            u.student_id
+            FROM users u
+            JOIN class_members cm ON cm.user_id = u.id
+            WHERE cm.class_id = ? AND cm.role = 'student'
+            ORDER BY u.name"#,
+            class_id,
+        )
+        .fetch_all(db)
+        .await
+    }
+
+    /// Takes the user out of the class along with what they were assigned in it, their
+    /// attempts are kept.
+    pub async fn remove(db: &SqlitePool, class_id: Uuid, user_id: Uuid) -> sqlx::Result<()> {
+        let mut tx = db.begin().await?;
+
+        sqlx::query!(
+            "DELETE FROM user_assignments WHERE class_id = ? AND user_id = ?",
+            class_id,
+            user_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM class_members WHERE class_id = ? AND user_id = ?",
+            class_id,
+            user_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await
+    }
+
+    /// Moves the student to another class, along with what they were assigned, so their
+    /// attempts and points follow them.
+    pub async fn move_student(
+        db: &SqlitePool,
+        from: Uuid,
+        to: Uuid,
+        user_id: Uuid,
+    ) -> sqlx::Result<()> {
+        let mut tx = db.begin().await?;
+
+        sqlx::query!(
+            "INSERT INTO class_members (class_id, user_id, role) VALUES (?, ?, 'student') ON CONFLICT DO NOTHING",
+            to,
+            user_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // assignments they already have in the other class stay where they are
+        sqlx::query!(
+            "UPDATE OR IGNORE user_assignments SET class_id = ? WHERE class_id = ? AND user_id = ?",
+            to,
+            from,
+            user_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM user_assignments WHERE class_id = ? AND user_id = ?",
+            from,
+            user_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM class_members WHERE class_id = ? AND user_id = ?",
+            from,
+            user_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::SqlitePool;
+    use uuid::Uuid;
+
+    use super::{ClassMember, ClassRole};
+    use crate::{
+        models::{Class, User},
+        testing,
+    };
+
+    /// Assignments the user has in the class.
+    async fn assigned(db: &SqlitePool, class_id: Uuid, user: &User) -> Vec<Uuid> {
+        let mut assignments = sqlx::query_scalar!(
+            r#"SELECT assignment_id as "assignment_id: Uuid" FROM user_assignments
+            WHERE class_id = ? AND user_id = ?"#,
+            class_id,
+            user.id,
+        )
+        .fetch_all(db)
+        .await
+        .expect("assignments listed");
+
+        assignments.sort();
+        assignments
+    }
+
+    async fn role(db: &SqlitePool, class_id: Uuid, user: &User) -> Option<ClassRole> {
+        ClassMember::role_of(db, class_id, user.id)
+            .await
+            .expect("role read")
+    }
+
+    #[tokio::test]
+    async fn moved_student_takes_their_assignments_along() {
+        let db = testing::db().await;
+        let teacher = testing::user(&db, "teacher").await;
+        let student = testing::user(&db, "student").await;
+
+        let from = testing::class(&db, &teacher).await;
+        let to = testing::class(&db, &teacher).await;
+        let moved = testing::assignment(&db).await;
+        let shared = testing::assignment(&db).await;
+        testing::test(&db, moved, 3).await;
+        testing::test(&db, shared, 2).await;
+
+        testing::assign(&db, from, moved, &student).await;
+        testing::assign(&db, from, shared, &student).await;
+        testing::assign(&db, to, shared, &student).await;
+
+        ClassMember::move_student(&db, from, to, student.id)
+            .await
+            .expect("student moved");
+
+        let mut expected = vec![moved, shared];
+        expected.sort();
+
+        assert_eq!(role(&db, from, &student).await, None);
+        assert_eq!(role(&db, to, &student).await, Some(ClassRole::Student));
+        assert!(assigned(&db, from, &student).await.is_empty());
+        assert_eq!(assigned(&db, to, &student).await, expected);
+
+        let points = Class::points_for_student(&db, to, student.id)
+            .await
+            .expect("points summed");
+        assert_eq!(points.maximum(), 5);
+    }
+
+    #[tokio::test]
+    async fn joining_keeps_the_role_of_members() {
+        let db = testing::db().await;
+        let teacher = testing::user(&db, "teacher").await;
+        let ta = testing::user(&db, "ta").await;
+        let class_id = testing::class(&db, &teacher).await;
+        testing::member(&db, class_id, &ta, ClassRole::Ta).await;
+
+        // what joining with the code does
+        for user in [&teacher, &ta] {
+            ClassMember::add(&db, class_id, user.id, ClassRole::Student)
+                .await
+                .expect("member added");
+        }
+
+        assert_eq!(
+            role(&db, class_id, &teacher).await,
+            Some(ClassRole::Teacher)
+        );
+        assert_eq!(role(&db, class_id, &ta).await, Some(ClassRole::Ta));
+
+        ClassMember::set_role(&db, class_id, ta.id, ClassRole::Student)
+            .await
+            .expect("role set");
+        assert_eq!(role(&db, class_id, &ta).await, Some(ClassRole::Student));
+    }
+}
@@ -0,0 +1,289 @@
+use chrono::{NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::{models::NetworkPolicy, sandbox::SandboxOutput};
+
+/// How the build of an attempt ended.
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum CompilationOutcome {
+    Succeeded,
+    /// The compiler rejected the program
+    Failed,
+    /// The build could not be run at all, see [`Compilation::error_reason`]
+    SystemError,
+    /// Stopped before finishing, because the attempt was superseded or cancelled by a teacher
+    Cancelled,
+}
+
+/// The build step of an attempt, ran once before any of its tests.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Compilation {
+    pub id: Uuid,
+
+    pub attempt_id: Uuid,
+
+    pub succeeded: bool,
+    /// `None` while the build is queued or running
+    pub outcome: Option<CompilationOutcome>,
+
+    pub command_ran: String,
+    pub created_at: NaiveDateTime,
+
+    pub started_at: Option<NaiveDateTime>,
+    pub finished_at: Option<NaiveDateTime>,
+    pub exit_code: Option<i64>,
+    pub stdout: Option<Vec<u8>>,
+    pub stderr: Option<Vec<u8>>,
+
+    pub memory_limit: i64,
+    pub time_limit: i64,
+    pub max_cpus: i64,
+    pub network_policy: NetworkPolicy,
+
+    pub sandbox_config: Option<String>,
+    /// Why the build could not be run, set while it is retried or once it gave up
+    pub error_reason: Option<String>,
+}
+
+impl Compilation {
+    pub async fn insert_new(&self, pool: &SqlitePool) -> sqlx::Result<()> {
+        sqlx::query!(
+            "INSERT INTO compilations (id, attempt_id, succeeded, command_ran, created_at, memory_limit, time_limit, max_cpus, network_policy) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            self.id,
+            self.attempt_id,
+            self.succeeded,
+            self.command_ran,
+            self.created_at,
+            self.memory_limit,
+            self.time_limit,
+            self.max_cpus,
+            self.network_policy,
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn mark_started(&self, pool: &SqlitePool) -> sqlx::Result<()> {
+        let now = Utc::now().naive_utc();
+
+        sqlx::query!(
+            "UPDATE compilations SET started_at = ? WHERE id = ?",
+            now,
+            self.id
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Puts a compilation that failed for a reason unrelated to the submission back to queued.
+    pub async fn requeue_after_error(
+        pool: &SqlitePool,
+        id: Uuid,
+        reason: &str,
+    ) -> sqlx::Result<()> {
+        sqlx::query!(
+            "UPDATE compilations SET started_at = NULL, error_reason = ? WHERE id = ? AND finished_at IS NULL",
+            reason,
+            id,
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Gives up on the compilation, finishing it as failed.
+    pub async fn finish_system_error(
+        pool: &SqlitePool,
+        id: Uuid,
+        reason: &str,
+    ) -> sqlx::Result<()> {
+        let now = Utc::now().naive_utc();
+
+        sqlx::query!(
+            "UPDATE compilations SET finished_at = ?, succeeded = false, outcome = ?, error_reason = ? WHERE id = ? AND finished_at IS NULL",
+            now,
+            CompilationOutcome::SystemError,
+            reason,
+            id,
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Finishes the compilation of the attempt as cancelled if it is still queued or running.
+    pub async fn cancel_unfinished(pool: &SqlitePool, attempt_id: Uuid) -> sqlx::Result<()> {
+        let now = Utc::now().naive_utc();
+
+        sqlx::query!(
+            "UPDATE compilations SET finished_at = ?, succeeded = false, outcome = ? WHERE attempt_id = ? AND finished_at IS NULL",
+            now,
+            CompilationOutcome::Cancelled,
+            attempt_id,
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Records the finished build, returns `false` if the compilation was finished meanwhile,
+    /// e.g. cancelled, and is left as it is.
+    pub async fn update_completed(
+        self,
+        pool: &SqlitePool,
+        output: SandboxOutput,
+        succeeded: bool,
+    ) -> sqlx::Result<bool> {
+        let now = Utc::now().naive_utc();
+        let outcome = if succeeded {
+            CompilationOutcome::Succeeded
+        } else {
+            CompilationOutcome::Failed
+        };
+
+        let result = sqlx::query!(
+            "UPDATE compilations SET finished_at = ?, exit_code = ?, stdout = ?, stderr = ?, succeeded = ?, outcome = ?, sandbox_config = ?, error_reason = NULL WHERE id = ? AND finished_at IS NULL",
+            now,
+            output.exit_code,
+            output.stdout,
+            output.stderr,
+            succeeded,
+            outcome,
+            output.config,
+            self.id,
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn by_id(db: &SqlitePool, id: Uuid) -> sqlx::Result<Self> {
+        sqlx::query_as!(
+            Compilation,
+            r#"SELECT
+                id as "id: Uuid",
+                attempt_id as "attempt_id: Uuid",
+                succeeded,
+                outcome as "outcome: CompilationOutcome",
+                command_ran,
+                created_at as "created_at: chrono::NaiveDateTime",
+                started_at as "started_at: chrono::NaiveDateTime",
+                finished_at as "finished_at: chrono::NaiveDateTime",
+                exit_code,
+                stdout,
+                stderr,
+                memory_limit,
+                time_limit,
+                max_cpus,
+                network_policy as "network_policy: NetworkPolicy",
+                sandbox_config,
+                error_reason
+            FROM compilations
+            WHERE id = ?"#,
+            id
+        )
+        .fetch_one(db)
+        .await
+    }
+
+    pub async fn latest_for_attempt(
+        db: &SqlitePool,
+        attempt_id: Uuid,
+    ) -> sqlx::Result<Option<Self>> {
+        sqlx::query_as!(
+            Compilation,
+            r#"SELECT
+                id as "id: Uuid",
+                attempt_id as "attempt_id: Uuid",
+                succeeded,
+                outcome as "outcome: CompilationOutcome",
+                command_ran,
+                created_at as "created_at: chrono::NaiveDateTime",
+                started_at as "started_at: chrono::NaiveDateTime",
+                finished_at as "finished_at: chrono::NaiveDateTime",
+                exit_code,
+                stdout,
+                stderr,
+                memory_limit,
+                time_limit,
+                max_cpus,
+                network_policy as "network_policy: NetworkPolicy",
+                sandbox_config,
+                error_reason
+            FROM compilations
+            WHERE attempt_id = ?
+            ORDER BY created_at DESC
+            LIMIT 1"#,
+            attempt_id
+        )
+        .fetch_optional(db)
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cgroup::ResourceUsage,
+        models::{Compilation, CompilationOutcome},
+        sandbox::SandboxOutput,
+        testing,
+    };
+
+    #[tokio::test]
+    async fn build_finishing_after_cancel_keeps_cancelled() {
+        let db = testing::db().await;
+        let student = testing::user(&db, "student").await;
+        let assignment_id = testing::assignment(&db).await;
+        let attempt = testing::attempt(&db, assignment_id, &student).await;
+
+        let compilation = testing::blueprint().pending_compilation(attempt.id);
+        let compilation_id = compilation.id;
+        compilation
+            .insert_new(&db)
+            .await
+            .expect("compilation inserted");
+
+        Compilation::cancel_unfinished(&db, attempt.id)
+            .await
+            .expect("compilation cancelled");
+
+        let output = SandboxOutput {
+            exit_code: Some(0),
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+            usage: ResourceUsage::default(),
+            config: None,
+            sandbox_error: None,
+        };
+
+        let compilation = Compilation::by_id(&db, compilation_id)
+            .await
+            .expect("compilation exists");
+
+        let recorded = compilation
+            .update_completed(&db, output, true)
+            .await
+            .expect("update ran");
+
+        let compilation = Compilation::by_id(&db, compilation_id)
+            .await
+            .expect("compilation exists");
+
+        assert!(!recorded);
+        assert_eq!(compilation.outcome, Some(CompilationOutcome::Cancelled));
+        assert_eq!(compilation.error_reason, None);
+    }
+}
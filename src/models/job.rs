@@ -0,0 +1,498 @@
+use std::collections::HashMap;
+
+use chrono::{NaiveDateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::models::{Compilation, Runner};
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    Compile,
+    Test,
+}
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Done,
+    Cancelled,
+}
+
+impl JobStatus {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::Running => "running",
+            Self::Done => "done",
+            Self::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// Jobs of a lane are only handed out once no job of a more important lane is waiting.
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum JobLane {
+    /// Attempts submitted by students
+    Normal,
+    /// Re-evaluations started by teachers, nobody is waiting on those
+    Rejudge,
+}
+
+/// A compilation or runner waiting for, or held by, a judge worker.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Job {
+    pub id: Uuid,
+    pub kind: JobKind,
+
+    pub attempt_id: Uuid,
+    /// Author of the attempt, jobs are handed out round-robin across authors
+    pub user_id: Uuid,
+    pub lane: JobLane,
+    /// Compilation the job fills in, set for compile jobs
+    pub compilation_id: Option<Uuid>,
+    /// Runner the job fills in, set for test jobs
+    pub runner_id: Option<Uuid>,
+
+    pub status: JobStatus,
+    pub tries: i64,
+    pub queued_at: NaiveDateTime,
+    pub claimed_at: Option<NaiveDateTime>,
+    /// `evaltor-worker` that claimed the job last, `None` for the server's own judge slots
+    pub worker: Option<String>,
+
+    pub leased_until: Option<NaiveDateTime>,
+    pub heartbeat_at: Option<NaiveDateTime>,
+}
+
+impl Job {
+    pub fn compile(compilation: &Compilation, user_id: Uuid, lane: JobLane) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            kind: JobKind::Compile,
+            attempt_id: compilation.attempt_id,
+            user_id,
+            lane,
+            compilation_id: Some(compilation.id),
+            runner_id: None,
+            status: JobStatus::Queued,
+            tries: 0,
+            queued_at: Utc::now().naive_utc(),
+            claimed_at: None,
+            worker: None,
+            leased_until: None,
+            heartbeat_at: None,
+        }
+    }
+
+    /// Test job for the runner, ordered in the queue as if it was queued at `queued_at`.
+    pub fn test(runner: &Runner, user_id: Uuid, lane: JobLane, queued_at: NaiveDateTime) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            kind: JobKind::Test,
+            attempt_id: runner.attempt_id,
+            user_id,
+            lane,
+            compilation_id: None,
+            runner_id: Some(runner.id),
+            status: JobStatus::Queued,
+            tries: 0,
+            queued_at,
+            claimed_at: None,
+            worker: None,
+            leased_until: None,
+            heartbeat_at: None,
+        }
+    }
+
+    pub async fn insert_new(&self, pool: &SqlitePool) -> sqlx::Result<()> {
+        sqlx::query!(
+            "INSERT INTO jobs (id, kind, attempt_id, user_id, lane, compilation_id, runner_id, status, tries, queued_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            self.id,
+            self.kind,
+            self.attempt_id,
+            self.user_id,
+            self.lane,
+            self.compilation_id,
+            self.runner_id,
+            self.status,
+            self.tries,
+            self.queued_at,
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Takes the next job for `lease` on behalf of `worker`, a queued one or a running one
+    /// whose lease ran out.
+    ///
+    /// Within the most important lane with anything waiting, the job goes to the user who was
+    /// served least recently, so a user submitting over and over only delays themselves.
+    pub async fn claim(
+        pool: &SqlitePool,
+        lease: TimeDelta,
+        worker: Option<&str>,
+    ) -> sqlx::Result<Option<Self>> {
+        let now = Utc::now().naive_utc();
+        let leased_until = now + lease;
+
+        sqlx::query_as!(
+            Job,
+            r#"UPDATE jobs
+            SET status = 'running', tries = tries + 1, claimed_at = ?2, leased_until = ?1, heartbeat_at = ?2, worker = ?3
+            WHERE id = (
+                SELECT j.id FROM jobs j
+                WHERE j.status = 'queued' OR (j.status = 'running' AND j.leased_until < ?2)
+                ORDER BY
+                    j.lane = 'rejudge',
+                    (SELECT max(served.claimed_at) FROM jobs served WHERE served.user_id = j.user_id),
+                    j.queued_at,
+                    j.rowid
+                LIMIT 1
+            )
+            RETURNING
+                id as "id!: Uuid",
+                kind as "kind!: JobKind",
+                attempt_id as "attempt_id!: Uuid",
+                user_id as "user_id!: Uuid",
+                lane as "lane!: JobLane",
+                compilation_id as "compilation_id: Uuid",
+                runner_id as "runner_id: Uuid",
+                status as "status!: JobStatus",
+                tries as "tries!",
+                queued_at as "queued_at!: NaiveDateTime",
+                claimed_at as "claimed_at: NaiveDateTime",
+                worker,
+                leased_until as "leased_until: NaiveDateTime",
+                heartbeat_at as "heartbeat_at: NaiveDateTime""#,
+            leased_until,
+            now,
+            worker,
+        )
+        .fetch_optional(pool)
+        .await
+    }
+
+    pub async fn by_id(pool: &SqlitePool, id: Uuid) -> sqlx::Result<Option<Self>> {
+        sqlx::query_as!(
+            Job,
+            r#"SELECT
+                id as "id: Uuid",
+                kind as "kind: JobKind",
+                attempt_id as "attempt_id: Uuid",
+                user_id as "user_id!: Uuid",
+                lane as "lane: JobLane",
+                compilation_id as "compilation_id: Uuid",
+                runner_id as "runner_id: Uuid",
+                status as "status: JobStatus",
+                tries,
+                queued_at as "queued_at: NaiveDateTime",
+                claimed_at as "claimed_at: NaiveDateTime",
+                worker,
+                leased_until as "leased_until: NaiveDateTime",
+                heartbeat_at as "heartbeat_at: NaiveDateTime"
+            FROM jobs WHERE id = ?"#,
+            id
+        )
+        .fetch_optional(pool)
+        .await
+    }
+
+    /// Extends the lease of a running job, so it is not claimed by anyone else.
+    pub async fn heartbeat(&self, pool: &SqlitePool, lease: TimeDelta) -> sqlx::Result<()> {
+        let now = Utc::now().naive_utc();
+        let leased_until = now + lease;
+
+        sqlx::query!(
+            "UPDATE jobs SET leased_until = ?, heartbeat_at = ? WHERE id = ? AND status = 'running'",
+            leased_until,
+            now,
+            self.id,
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn finish(&self, pool: &SqlitePool) -> sqlx::Result<()> {
+        sqlx::query!(
+            "UPDATE jobs SET status = 'done', leased_until = NULL WHERE id = ? AND status = 'running'",
+            self.id
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Puts the job back into the queue, keeping its place and the number of tries.
+    pub async fn requeue(&self, pool: &SqlitePool) -> sqlx::Result<()> {
+        sqlx::query!(
+            "UPDATE jobs SET status = 'queued', leased_until = NULL WHERE id = ? AND status = 'running'",
+            self.id
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Puts a job that was killed through no fault of its own back into the queue, giving it
+    /// back the try. Returns `false` if the job was not running anymore.
+    pub async fn requeue_interrupted(&self, pool: &SqlitePool) -> sqlx::Result<bool> {
+        let result = sqlx::query!(
+            "UPDATE jobs SET status = 'queued', leased_until = NULL, tries = tries - 1 WHERE id = ? AND status = 'running'",
+            self.id
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Takes every unfinished job of the attempt out of the queue, even the running ones.
+    pub async fn cancel_for_attempt(pool: &SqlitePool, attempt_id: Uuid) -> sqlx::Result<u64> {
+        let result = sqlx::query!(
+            "UPDATE jobs SET status = 'cancelled', leased_until = NULL WHERE attempt_id = ? AND status IN ('queued', 'running')",
+            attempt_id
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Puts every job left running by a previous run of the server back into the queue,
+    /// giving back the try like [`Job::requeue_interrupted`] and showing their runs as waiting.
+    ///
+    /// Jobs held by `evaltor-worker`s are left alone, they may still be running there.
+    pub async fn requeue_running(pool: &SqlitePool) -> sqlx::Result<u64> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query!(
+            "UPDATE runners SET started_at = NULL WHERE finished_at IS NULL AND id IN (
+                SELECT runner_id FROM jobs WHERE status = 'running' AND worker IS NULL
+            )"
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE compilations SET started_at = NULL WHERE finished_at IS NULL AND id IN (
+                SELECT compilation_id FROM jobs WHERE status = 'running' AND worker IS NULL
+            )"
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let result = sqlx::query!(
+            "UPDATE jobs SET status = 'queued', leased_until = NULL, tries = tries - 1 WHERE status = 'running' AND worker IS NULL"
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// 1-based position in the queue of the first waiting job of every attempt.
+    ///
+    /// Uses the order [`Job::claim`] would hand the jobs out in right now, which shifts as
+    /// users get served.
+    pub async fn queue_positions(pool: &SqlitePool) -> sqlx::Result<HashMap<Uuid, usize>> {
+        let queued = sqlx::query!(
+            r#"SELECT j.attempt_id as "attempt_id: Uuid"
+            FROM jobs j
+            WHERE j.status = 'queued'
+            ORDER BY
+                j.lane = 'rejudge',
+                (SELECT max(served.claimed_at) FROM jobs served WHERE served.user_id = j.user_id),
+                j.queued_at,
+                j.rowid"#
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let mut positions = HashMap::new();
+
+        for (index, job) in queued.into_iter().enumerate() {
+            positions.entry(job.attempt_id).or_insert(index + 1);
+        }
+
+        Ok(positions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDateTime, TimeDelta, Utc};
+    use sqlx::SqlitePool;
+    use uuid::Uuid;
+
+    use super::{Job, JobLane, JobStatus};
+    use crate::{
+        models::{Runner, User},
+        testing,
+    };
+
+    const LEASE: TimeDelta = TimeDelta::seconds(60);
+
+    /// Queues a test job for a new attempt of `user`, `minutes_ago` minutes in the past.
+    async fn queue(db: &SqlitePool, user: &User, lane: JobLane, minutes_ago: i64) -> Uuid {
+        let assignment_id = testing::assignment(db).await;
+        let test_id = testing::test(db, assignment_id, 1).await;
+        let attempt = testing::attempt(db, assignment_id, user).await;
+        let runner = testing::runner(db, &attempt, test_id).await;
+
+        let queued_at: NaiveDateTime = Utc::now().naive_utc() - TimeDelta::minutes(minutes_ago);
+        let job = Job::test(&runner, user.id, lane, queued_at);
+        job.insert_new(db).await.expect("job inserted");
+
+        job.id
+    }
+
+    async fn claim(db: &SqlitePool, lease: TimeDelta) -> Option<Job> {
+        Job::claim(db, lease, None).await.expect("claim ran")
+    }
+
+    #[tokio::test]
+    async fn users_are_served_round_robin() {
+        let db = testing::db().await;
+        let busy = testing::user(&db, "busy").await;
+        let other = testing::user(&db, "other").await;
+
+        let busy_first = queue(&db, &busy, JobLane::Normal, 30).await;
+        let busy_second = queue(&db, &busy, JobLane::Normal, 20).await;
+        let busy_third = queue(&db, &busy, JobLane::Normal, 15).await;
+        let other_first = queue(&db, &other, JobLane::Normal, 10).await;
+
+        let mut claimed = Vec::new();
+        while let Some(job) = claim(&db, LEASE).await {
+            claimed.push(job.id);
+        }
+
+        assert_eq!(claimed, [busy_first, other_first, busy_second, busy_third]);
+    }
+
+    #[tokio::test]
+    async fn rejudges_wait_for_submissions() {
+        let db = testing::db().await;
+        let student = testing::user(&db, "student").await;
+        let other = testing::user(&db, "other").await;
+
+        let rejudge = queue(&db, &student, JobLane::Rejudge, 60).await;
+        let submission = queue(&db, &other, JobLane::Normal, 1).await;
+
+        assert_eq!(claim(&db, LEASE).await.map(|job| job.id), Some(submission));
+        assert_eq!(claim(&db, LEASE).await.map(|job| job.id), Some(rejudge));
+    }
+
+    #[tokio::test]
+    async fn expired_lease_is_claimed_again() {
+        let db = testing::db().await;
+        let student = testing::user(&db, "student").await;
+        let job_id = queue(&db, &student, JobLane::Normal, 1).await;
+
+        // the worker holding the job crashed right away
+        let crashed = claim(&db, TimeDelta::seconds(-1))
+            .await
+            .expect("job claimed");
+        assert_eq!((crashed.id, crashed.tries), (job_id, 1));
+
+        let retried = claim(&db, LEASE).await.expect("job claimed again");
+        assert_eq!((retried.id, retried.tries), (job_id, 2));
+
+        assert!(claim(&db, LEASE).await.is_none(), "lease is held");
+    }
+
+    #[tokio::test]
+    async fn heartbeat_keeps_the_lease() {
+        let db = testing::db().await;
+        let student = testing::user(&db, "student").await;
+        queue(&db, &student, JobLane::Normal, 1).await;
+
+        let job = claim(&db, TimeDelta::seconds(-1))
+            .await
+            .expect("job claimed");
+        job.heartbeat(&db, LEASE).await.expect("heartbeat ran");
+
+        assert!(claim(&db, LEASE).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn requeued_jobs_keep_their_tries() {
+        let db = testing::db().await;
+        let student = testing::user(&db, "student").await;
+        queue(&db, &student, JobLane::Normal, 1).await;
+
+        let failed = claim(&db, LEASE).await.expect("job claimed");
+        failed.requeue(&db).await.expect("job requeued");
+
+        let interrupted = claim(&db, LEASE).await.expect("job claimed again");
+        assert_eq!(interrupted.tries, 2);
+
+        // a shutdown is no fault of the job, it gets the try back
+        assert!(
+            interrupted
+                .requeue_interrupted(&db)
+                .await
+                .expect("job requeued")
+        );
+
+        let job = Job::by_id(&db, interrupted.id)
+            .await
+            .expect("job fetched")
+            .expect("job exists");
+        assert_eq!((job.status, job.tries), (JobStatus::Queued, 1));
+    }
+
+    #[tokio::test]
+    async fn restart_requeues_only_local_jobs() {
+        let db = testing::db().await;
+        let student = testing::user(&db, "student").await;
+        let local = queue(&db, &student, JobLane::Normal, 2).await;
+        let remote = queue(&db, &student, JobLane::Normal, 1).await;
+
+        let running = claim(&db, LEASE).await.expect("local job claimed");
+        let runner_id = running.runner_id.expect("test job");
+        Runner::by_id(&db, runner_id)
+            .await
+            .expect("runner fetched")
+            .mark_started(&db)
+            .await
+            .expect("runner started");
+        Job::claim(&db, LEASE, Some("worker-1"))
+            .await
+            .expect("claim ran")
+            .expect("remote job claimed");
+
+        assert_eq!(Job::requeue_running(&db).await.expect("requeue ran"), 1);
+
+        let state = async |id| {
+            Job::by_id(&db, id)
+                .await
+                .expect("job fetched")
+                .map(|job| (job.status, job.tries))
+        };
+
+        // the restart is no fault of the job, it gets the try back
+        assert_eq!(state(local).await, Some((JobStatus::Queued, 0)));
+        assert_eq!(state(remote).await, Some((JobStatus::Running, 1)));
+
+        let runner = Runner::by_id(&db, runner_id).await.expect("runner fetched");
+        assert_eq!(runner.started_at, None);
+    }
+}
@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Debug, sqlx::FromRow)]
+pub struct Language {
+    pub id: Uuid,
+
+    pub name: String,
+    /// File name the uploaded program is stored under, relative to the workspace
+    pub source_file: String,
+
+    pub compile_command: Option<String>,
+    pub run_command: String,
+
+    pub memory_limit: i64,
+    pub time_limit: i64,
+
+    pub compile_memory_limit: i64,
+    pub compile_time_limit: i64,
+
+    /// Root filesystem the language runs in, relative to the configured rootfs directory
+    pub rootfs: String,
+}
+
+impl Language {
+    pub async fn enabled_for_assignment(
+        db: &SqlitePool,
+        assignment_id: Uuid,
+    ) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Language,
+            r#"SELECT
+                l.id as "id: Uuid",
+                l.name,
+                l.source_file,
+                l.compile_command,
+                l.run_command,
+                l.memory_limit,
+                l.time_limit,
+                l.compile_memory_limit,
+                l.compile_time_limit,
+                l.rootfs
+            FROM languages l
+            JOIN assignment_languages al ON al.language_id = l.id
+            WHERE al.assignment_id = ?
+            ORDER BY l.name"#,
+            assignment_id
+        )
+        .fetch_all(db)
+        .await
+    }
+
+    /// Returns the language only if it is enabled for the given assignment.
+    pub async fn enabled_by_id(
+        db: &SqlitePool,
+        assignment_id: Uuid,
+        language_id: Uuid,
+    ) -> sqlx::Result<Option<Self>> {
+        sqlx::query_as!(
+            Language,
+            r#"SELECT
+                l.id as "id: Uuid",
+                l.name,
+                l.source_file,
+                l.compile_command,
+                l.run_command,
+                l.memory_limit,
+                l.time_limit,
+                l.compile_memory_limit,
+                l.compile_time_limit,
+                l.rootfs
+            FROM languages l
+            JOIN assignment_languages al ON al.language_id = l.id
+            WHERE al.assignment_id = ? AND l.id = ?"#,
+            assignment_id,
+            language_id
+        )
+        .fetch_optional(db)
+        .await
+    }
+
+    pub async fn for_attempt(db: &SqlitePool, attempt_id: Uuid) -> sqlx::Result<Self> {
+        sqlx::query_as!(
+            Language,
+            r#"SELECT
+                l.id as "id: Uuid",
+                l.name,
+                l.source_file,
+                l.compile_command,
+                l.run_command,
+                l.memory_limit,
+                l.time_limit,
+                l.compile_memory_limit,
+                l.compile_time_limit,
+                l.rootfs
+            FROM languages l
+            JOIN attempts a ON a.language_id = l.id
+            WHERE a.id = ?"#,
+            attempt_id
+        )
+        .fetch_one(db)
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Language;
+    use crate::testing;
+
+    #[tokio::test]
+    async fn new_assignments_can_be_submitted_in_python() {
+        let db = testing::db().await;
+        let assignment_id = testing::assignment(&db).await;
+
+        let languages = Language::enabled_for_assignment(&db, assignment_id)
+            .await
+            .expect("languages listed");
+        let ids: Vec<_> = languages.iter().map(|language| language.id).collect();
+
+        assert_eq!(ids, [testing::PYTHON]);
+    }
+}
@@ -0,0 +1,37 @@
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::models::NetworkPolicy;
+
+/// Resource limits a test runs under.
+///
+/// Every limit is taken from the test if set there, otherwise from its assignment and finally
+/// from the language the attempt was submitted in.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    pub memory_limit: i64,
+    pub time_limit: i64,
+    pub max_cpus: i64,
+    pub network_policy: NetworkPolicy,
+}
+
+impl Limits {
+    pub async fn for_test(db: &SqlitePool, test_id: Uuid, language_id: Uuid) -> sqlx::Result<Self> {
+        sqlx::query_as!(
+            Limits,
+            r#"SELECT
+                COALESCE(t.memory_limit, a.memory_limit, l.memory_limit) as "memory_limit!: i64",
+                COALESCE(t.time_limit, a.time_limit, l.time_limit) as "time_limit!: i64",
+                COALESCE(t.max_cpus, a.max_cpus) as "max_cpus!: i64",
+                COALESCE(t.network_policy, a.network_policy) as "network_policy!: NetworkPolicy"
+            FROM tests t
+            JOIN assignments a ON a.id = t.assignment_id
+            JOIN languages l ON l.id = ?
+            WHERE t.id = ?"#,
+            language_id,
+            test_id
+        )
+        .fetch_one(db)
+        .await
+    }
+}
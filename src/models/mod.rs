@@ -1,15 +1,33 @@
 pub use assignment::Assignment;
 pub use attempt::Attempt;
 pub use class::Class;
+pub use class_member::{ClassMember, ClassRole};
+pub use compilation::{Compilation, CompilationOutcome};
+pub use job::{Job, JobKind, JobLane, JobStatus};
+pub use language::Language;
+pub use limits::Limits;
+pub use network_policy::NetworkPolicy;
+pub use pending_enrollment::{PendingEnrollment, RosterRow};
 pub use runner::Runner;
+pub use submission_quota::SubmissionQuota;
 pub use test::{Test, TestType};
 pub use user::User;
 pub use user_assignments::UserAssignment;
+pub use verdict::Verdict;
 
 mod assignment;
 mod attempt;
 mod class;
+mod class_member;
+mod compilation;
+mod job;
+mod language;
+mod limits;
+mod network_policy;
+mod pending_enrollment;
 mod runner;
+mod submission_quota;
 mod test;
 mod user;
 mod user_assignments;
+mod verdict;
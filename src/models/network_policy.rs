@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+/// Network access of a sandboxed run.
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum NetworkPolicy {
+    /// Own network namespace without any interfaces
+    Isolated,
+    /// Own network namespace with only the loopback interface up
+    Loopback,
+    /// The host's network
+    Host,
+}
+
+impl NetworkPolicy {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Isolated => "isolated",
+            Self::Loopback => "loopback",
+            Self::Host => "host",
+        }
+    }
+}
@@ -0,0 +1,388 @@
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+/// A student imported into a class before their first login, enrolled once someone signs in
+/// with the email.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PendingEnrollment {
+    pub class_id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub student_id: Option<String>,
+}
+
+/// A line of a roster CSV, with an `email`, `name` and `student_id` header.
+#[derive(Deserialize, Debug)]
+pub struct RosterRow {
+    pub email: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub student_id: Option<String>,
+}
+
+/// How many students of an imported roster were enrolled right away and how many wait for
+/// their first login.
+#[derive(Debug, Default)]
+pub struct RosterImport {
+    pub enrolled: usize,
+    pub pending: usize,
+}
+
+impl RosterRow {
+    /// Reads the roster, the error names the line that could not be read.
+    pub fn parse(csv: &[u8]) -> Result<Vec<Self>, String> {
+        csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(csv)
+            .deserialize::<Self>()
+            .map(|row| {
+                let mut row = row.map_err(|err| err.to_string())?;
+
+                row.email = row.email.to_lowercase();
+                row.student_id = row.student_id.filter(|id| !id.is_empty());
+
+                if row.email.contains('@') {
+                    Ok(row)
+                } else {
+                    Err(format!("{:?} is not an email", row.email))
+                }
+            })
+            .collect()
+    }
+}
+
+impl PendingEnrollment {
+    pub async fn for_class(db: &SqlitePool, class_id: Uuid) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            PendingEnrollment,
+            r#"SELECT
+                class_id as "class_id: Uuid",
+                email,
+                name,
+                student_id
+            FROM pending_enrollments
+            WHERE class_id = ?
+            ORDER BY name, email"#,
+            class_id,
+        )
+        .fetch_all(db)
+        .await
+    }
+
+    /// Enrolls the students of the roster in the class as students, with the assignments.
+    ///
+    /// Those who have not signed in with an email their provider vouched for are kept pending
+    /// until they do, anyone could have claimed the email otherwise.
+    pub async fn import(
+        db: &SqlitePool,
+        class_id: Uuid,
+        rows: &[RosterRow],
+        assignment_ids: &[Uuid],
+    ) -> sqlx::Result<RosterImport> {
+        let mut import = RosterImport::default();
+        let mut tx = db.begin().await?;
+
+        for row in rows {
+            let user_id = sqlx::query_scalar!(
+                r#"SELECT id as "id: Uuid" FROM users WHERE lower(email) = ? AND email_verified"#,
+                row.email
+            )
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            let Some(user_id) = user_id else {
+                sqlx::query!(
+                    "INSERT INTO pending_enrollments (class_id, email, name, student_id) VALUES (?, ?, ?, ?)
+                    ON CONFLICT (class_id, email) DO UPDATE SET name = excluded.name, student_id = excluded.student_id",
+                    class_id,
+                    row.email,
+                    row.name,
+                    row.student_id,
+                )
+                .execute(&mut *tx)
+                .await?;
+
+                for assignment_id in assignment_ids {
+                    sqlx::query!(
+                        "INSERT INTO pending_assignments (class_id, email, assignment_id) VALUES (?, ?, ?) ON CONFLICT DO NOTHING",
+                        class_id,
+                        row.email,
+                        assignment_id,
+                    )
+                    .execute(&mut *tx)
+                    .await?;
+                }
+
+                import.pending += 1;
+                continue;
+            };
+
+            sqlx::query!(
+                "INSERT INTO class_members (class_id, user_id, role) VALUES (?, ?, 'student') ON CONFLICT DO NOTHING",
+                class_id,
+                user_id,
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            if let Some(student_id) = &row.student_id {
+                sqlx::query!(
+                    "UPDATE users SET student_id = ? WHERE id = ?",
+                    student_id,
+                    user_id
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+
+            for assignment_id in assignment_ids {
+                let id = Uuid::new_v4();
+
+                sqlx::query!(
+                    "INSERT INTO user_assignments (id, user_id, assignment_id, class_id) VALUES (?, ?, ?, ?) ON CONFLICT DO NOTHING",
+                    id,
+                    user_id,
+                    assignment_id,
+                    class_id,
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+
+            import.enrolled += 1;
+        }
+
+        tx.commit().await?;
+
+        Ok(import)
+    }
+
+    /// Enrolls the user in every class they were imported into under the email.
+    pub async fn claim(db: &SqlitePool, user_id: Uuid, email: &str) -> sqlx::Result<()> {
+        let email = email.to_lowercase();
+        let mut tx = db.begin().await?;
+
+        sqlx::query!(
+            "INSERT INTO class_members (class_id, user_id, role)
+            SELECT class_id, ?, 'student' FROM pending_enrollments WHERE email = ?
+            ON CONFLICT DO NOTHING",
+            user_id,
+            email,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let assignments = sqlx::query!(
+            r#"SELECT class_id as "class_id: Uuid", assignment_id as "assignment_id: Uuid"
+            FROM pending_assignments WHERE email = ?"#,
+            email,
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        for assignment in assignments {
+            let id = Uuid::new_v4();
+
+            sqlx::query!(
+                "INSERT INTO user_assignments (id, user_id, assignment_id, class_id) VALUES (?, ?, ?, ?) ON CONFLICT DO NOTHING",
+                id,
+                user_id,
+                assignment.assignment_id,
+                assignment.class_id,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query!(
+            "UPDATE users SET student_id = COALESCE(
+                (SELECT student_id FROM pending_enrollments WHERE email = ? AND student_id IS NOT NULL LIMIT 1),
+                student_id
+            ) WHERE id = ?",
+            email,
+            user_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!("DELETE FROM pending_assignments WHERE email = ?", email)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query!("DELETE FROM pending_enrollments WHERE email = ?", email)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::SqlitePool;
+    use uuid::Uuid;
+
+    use super::{PendingEnrollment, RosterRow};
+    use crate::{
+        models::{ClassMember, ClassRole, User},
+        testing,
+    };
+
+    fn row(email: &str, student_id: &str) -> RosterRow {
+        RosterRow {
+            email: email.to_owned(),
+            name: String::new(),
+            student_id: Some(student_id.to_owned()),
+        }
+    }
+
+    /// A user who signed in with an email their provider vouched for.
+    async fn verified(db: &SqlitePool, name: &str) -> User {
+        let user = testing::user(db, name).await;
+
+        sqlx::query!(
+            "UPDATE users SET email_verified = true WHERE id = ?",
+            user.id
+        )
+        .execute(db)
+        .await
+        .expect("email verified");
+
+        user
+    }
+
+    async fn assigned(db: &SqlitePool, class_id: Uuid, user: &User) -> Vec<Uuid> {
+        sqlx::query_scalar!(
+            r#"SELECT assignment_id as "assignment_id: Uuid" FROM user_assignments
+            WHERE class_id = ? AND user_id = ?"#,
+            class_id,
+            user.id,
+        )
+        .fetch_all(db)
+        .await
+        .expect("assignments listed")
+    }
+
+    async fn student_id(db: &SqlitePool, user: &User) -> Option<String> {
+        User::all(db)
+            .await
+            .expect("users listed")
+            .into_iter()
+            .find(|listed| listed.id == user.id)
+            .and_then(|listed| listed.student_id)
+    }
+
+    #[tokio::test]
+    async fn import_enrolls_only_users_with_a_vouched_email() {
+        let db = testing::db().await;
+        let teacher = testing::user(&db, "teacher").await;
+        let class_id = testing::class(&db, &teacher).await;
+        let assignment_id = testing::assignment(&db).await;
+
+        // signed in before as Known@example.com
+        let known = verified(&db, "Known").await;
+        let unverified = testing::user(&db, "unverified").await;
+
+        let rows = [
+            row("known@example.com", "1"),
+            row("unverified@example.com", "2"),
+            row("new@example.com", "3"),
+        ];
+
+        let import = PendingEnrollment::import(&db, class_id, &rows, &[assignment_id])
+            .await
+            .expect("roster imported");
+        assert_eq!((import.enrolled, import.pending), (1, 2));
+
+        let role = async |user: &User| {
+            ClassMember::role_of(&db, class_id, user.id)
+                .await
+                .expect("role read")
+        };
+
+        assert_eq!(role(&known).await, Some(ClassRole::Student));
+        assert_eq!(assigned(&db, class_id, &known).await, [assignment_id]);
+        assert_eq!(student_id(&db, &known).await.as_deref(), Some("1"));
+
+        assert_eq!(role(&unverified).await, None);
+        assert_eq!(student_id(&db, &unverified).await, None);
+
+        let pending = PendingEnrollment::for_class(&db, class_id)
+            .await
+            .expect("pending listed");
+        let mut emails: Vec<_> = pending.iter().map(|p| p.email.as_str()).collect();
+        emails.sort_unstable();
+        assert_eq!(emails, ["new@example.com", "unverified@example.com"]);
+    }
+
+    #[tokio::test]
+    async fn first_login_claims_the_pending_enrollment() {
+        let db = testing::db().await;
+        let teacher = testing::user(&db, "teacher").await;
+        let class_id = testing::class(&db, &teacher).await;
+        let assignment_id = testing::assignment(&db).await;
+
+        PendingEnrollment::import(
+            &db,
+            class_id,
+            &[row("new@example.com", "3")],
+            &[assignment_id],
+        )
+        .await
+        .expect("roster imported");
+
+        let student = verified(&db, "new").await;
+        PendingEnrollment::claim(&db, student.id, "New@Example.com")
+            .await
+            .expect("enrollment claimed");
+
+        assert_eq!(
+            ClassMember::role_of(&db, class_id, student.id)
+                .await
+                .expect("role read"),
+            Some(ClassRole::Student)
+        );
+        assert_eq!(assigned(&db, class_id, &student).await, [assignment_id]);
+        assert_eq!(student_id(&db, &student).await.as_deref(), Some("3"));
+        assert!(
+            PendingEnrollment::for_class(&db, class_id)
+                .await
+                .expect("pending listed")
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn roster_emails_are_lowercase_and_empty_ids_missing() {
+        let rows = RosterRow::parse(
+            b"email,name,student_id\n Jan.Novak@Example.com , Jan Novak ,12345\nanna@example.com,Anna,\n",
+        )
+        .expect("roster parsed");
+
+        let rows: Vec<_> = rows
+            .iter()
+            .map(|row| {
+                (
+                    row.email.as_str(),
+                    row.name.as_str(),
+                    row.student_id.as_deref(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            rows,
+            [
+                ("jan.novak@example.com", "Jan Novak", Some("12345")),
+                ("anna@example.com", "Anna", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn roster_without_emails_is_rejected() {
+        assert!(RosterRow::parse(b"email,name,student_id\nJan Novak,,\n").is_err());
+    }
+}
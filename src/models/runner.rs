@@ -3,6 +3,11 @@ use serde::{Deserialize, Serialize};
 use sqlx::SqlitePool;
 use uuid::Uuid;
 
+use crate::{
+    models::{NetworkPolicy, Verdict},
+    sandbox::SandboxOutput,
+};
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Runner {
     pub id: Uuid,
@@ -12,11 +17,13 @@ pub struct Runner {
 
     pub passed: bool,
     pub points: i64,
+    pub verdict: Option<Verdict>,
 
     pub command_ran: String,
     pub user_command_ran: String,
     pub created_at: NaiveDateTime,
 
+    pub started_at: Option<NaiveDateTime>,
     pub finished_at: Option<NaiveDateTime>,
     pub exit_code: Option<i64>,
     pub stdout: Option<Vec<u8>>,
@@ -27,59 +34,371 @@ pub struct Runner {
     pub memory_limit: i64,
     pub time_limit: i64,
     pub max_cpus: i64,
-    pub disable_network: bool,
+    pub network_policy: NetworkPolicy,
+
+    pub wall_time_ms: Option<i64>,
+    pub cpu_time_ms: Option<i64>,
+    pub peak_memory_kb: Option<i64>,
+
+    pub sandbox_config: Option<String>,
+    /// Why the run could not be judged, see [`Verdict::SystemError`]
+    pub error_reason: Option<String>,
 }
 
 impl Runner {
-    pub async fn insert_new(self, pool: &SqlitePool) -> sqlx::Result<()> {
+    pub async fn by_id(db: &SqlitePool, id: Uuid) -> sqlx::Result<Self> {
+        sqlx::query_as!(
+            Runner,
+            r#"SELECT
+                id as "id: uuid::Uuid",
+                test_id as "test_id: uuid::Uuid",
+                attempt_id as "attempt_id: uuid::Uuid",
+                passed,
+                points,
+                verdict as "verdict: Verdict",
+                command_ran,
+                user_command_ran,
+                created_at as "created_at: chrono::NaiveDateTime",
+                started_at as "started_at: chrono::NaiveDateTime",
+                finished_at as "finished_at: chrono::NaiveDateTime",
+                exit_code,
+                stdout,
+                stderr,
+                expected_stdout,
+                expected_stderr,
+                memory_limit,
+                time_limit,
+                max_cpus,
+                network_policy as "network_policy: NetworkPolicy",
+                wall_time_ms,
+                cpu_time_ms,
+                peak_memory_kb,
+                sandbox_config,
+                error_reason
+            FROM runners WHERE id = ?"#,
+            id
+        )
+        .fetch_one(db)
+        .await
+    }
+
+    /// Unfinished runners of the attempt that have no job executing them yet.
+    pub async fn unqueued_for_attempt(
+        db: &SqlitePool,
+        attempt_id: Uuid,
+    ) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Runner,
+            r#"SELECT
+                id as "id: uuid::Uuid",
+                test_id as "test_id: uuid::Uuid",
+                attempt_id as "attempt_id: uuid::Uuid",
+                passed,
+                points,
+                verdict as "verdict: Verdict",
+                command_ran,
+                user_command_ran,
+                created_at as "created_at: chrono::NaiveDateTime",
+                started_at as "started_at: chrono::NaiveDateTime",
+                finished_at as "finished_at: chrono::NaiveDateTime",
+                exit_code,
+                stdout,
+                stderr,
+                expected_stdout,
+                expected_stderr,
+                memory_limit,
+                time_limit,
+                max_cpus,
+                network_policy as "network_policy: NetworkPolicy",
+                wall_time_ms,
+                cpu_time_ms,
+                peak_memory_kb,
+                sandbox_config,
+                error_reason
+            FROM runners r
+            WHERE attempt_id = ?
+            AND finished_at IS NULL
+            AND NOT EXISTS (SELECT 1 FROM jobs j WHERE j.runner_id = r.id)"#,
+            attempt_id
+        )
+        .fetch_all(db)
+        .await
+    }
+
+    /// Points of the attempt from the runs that count, see the `scored_runners` view.
+    ///
+    /// A rejudge only changes them once it finished.
+    pub async fn scored_points(db: &SqlitePool, attempt_id: Uuid) -> sqlx::Result<i64> {
+        sqlx::query_scalar!(
+            r#"SELECT COALESCE(SUM(points), 0) as "points!: i64" FROM scored_runners WHERE attempt_id = ?"#,
+            attempt_id
+        )
+        .fetch_one(db)
+        .await
+    }
+
+    pub async fn insert_new(&self, pool: &SqlitePool) -> sqlx::Result<()> {
         sqlx::query!(
-            "INSERT INTO runners (id, test_id, attempt_id, passed, points, command_ran, user_command_ran, created_at, memory_limit, time_limit, max_cpus, disable_network) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            "INSERT INTO runners (id, test_id, attempt_id, passed, points, verdict, command_ran, user_command_ran, created_at, started_at, finished_at, memory_limit, time_limit, max_cpus, network_policy) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
             self.id,
             self.test_id,
             self.attempt_id,
             self.passed,
             self.points,
+            self.verdict,
             self.command_ran,
             self.user_command_ran,
             self.created_at,
+            self.started_at,
+            self.finished_at,
             self.memory_limit,
             self.time_limit,
             self.max_cpus,
-            self.disable_network,
+            self.network_policy,
         ).execute(pool).await?;
 
         Ok(())
     }
 
-    #[expect(clippy::too_many_arguments)]
+    pub async fn mark_started(&self, pool: &SqlitePool) -> sqlx::Result<()> {
+        let now = Utc::now().naive_utc();
+
+        sqlx::query!(
+            "UPDATE runners SET started_at = ? WHERE id = ?",
+            now,
+            self.id
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Finishes the runner with `verdict` without it ever having run.
+    pub async fn finish_early(self, pool: &SqlitePool, verdict: Verdict) -> sqlx::Result<()> {
+        let now = Utc::now().naive_utc();
+
+        sqlx::query!(
+            "UPDATE runners SET finished_at = ?, passed = false, points = 0, verdict = ? WHERE id = ?",
+            now,
+            verdict,
+            self.id,
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Puts a runner that failed for a reason unrelated to the submission back to queued.
+    pub async fn requeue_after_error(
+        pool: &SqlitePool,
+        id: Uuid,
+        reason: &str,
+    ) -> sqlx::Result<()> {
+        sqlx::query!(
+            "UPDATE runners SET started_at = NULL, error_reason = ? WHERE id = ? AND finished_at IS NULL",
+            reason,
+            id,
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Gives up on the runner, finishing it as a system error.
+    pub async fn finish_system_error(
+        pool: &SqlitePool,
+        id: Uuid,
+        reason: &str,
+    ) -> sqlx::Result<()> {
+        let now = Utc::now().naive_utc();
+
+        sqlx::query!(
+            "UPDATE runners SET finished_at = ?, passed = false, points = 0, verdict = ?, error_reason = ? WHERE id = ? AND finished_at IS NULL",
+            now,
+            Verdict::SystemError,
+            reason,
+            id,
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Finishes every runner of the attempt that is still queued or running as cancelled,
+    /// returns their IDs.
+    pub async fn cancel_unfinished(pool: &SqlitePool, attempt_id: Uuid) -> sqlx::Result<Vec<Uuid>> {
+        let now = Utc::now().naive_utc();
+
+        let cancelled = sqlx::query!(
+            r#"UPDATE runners SET finished_at = ?, passed = false, points = 0, verdict = ? WHERE attempt_id = ? AND finished_at IS NULL
+            RETURNING id as "id!: Uuid""#,
+            now,
+            Verdict::Cancelled,
+            attempt_id,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(cancelled.into_iter().map(|runner| runner.id).collect())
+    }
+
+    /// Records the judged run, returns `false` if the runner was finished meanwhile, e.g.
+    /// cancelled, and is left as it is.
     pub async fn update_completed(
         self,
         pool: &SqlitePool,
-        exit_code: Option<i32>,
-        stdout: Vec<u8>,
-        stderr: Vec<u8>,
+        output: SandboxOutput,
         expected_stdout: Option<Vec<u8>>,
         expected_stderr: Option<Vec<u8>>,
-        passed: bool,
+        verdict: Verdict,
         points: i64,
-    ) -> sqlx::Result<()> {
+    ) -> sqlx::Result<bool> {
         let now = Utc::now().naive_utc();
+        let passed = verdict == Verdict::Accepted;
+        let wall_time_ms = output.usage.wall_time_ms();
+        let cpu_time_ms = output.usage.cpu_time_ms();
 
-        sqlx::query!(
-            "UPDATE runners SET finished_at = ?, exit_code = ?, stdout = ?, stderr = ?, expected_stdout = ?, expected_stderr = ?, passed = ?, points = ? WHERE id = ?",
+        let result = sqlx::query!(
+            "UPDATE runners SET finished_at = ?, exit_code = ?, stdout = ?, stderr = ?, expected_stdout = ?, expected_stderr = ?, passed = ?, verdict = ?, points = ?, wall_time_ms = ?, cpu_time_ms = ?, peak_memory_kb = ?, sandbox_config = ?, error_reason = NULL WHERE id = ? AND finished_at IS NULL",
             now,
-            exit_code,
-            stdout,
-            stderr,
+            output.exit_code,
+            output.stdout,
+            output.stderr,
             expected_stdout,
             expected_stderr,
             passed,
+            verdict,
             points,
+            wall_time_ms,
+            cpu_time_ms,
+            output.usage.peak_memory_kb,
+            output.config,
             self.id,
         )
         .execute(pool)
         .await?;
 
-        Ok(())
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::SqlitePool;
+
+    use crate::{
+        cgroup::ResourceUsage,
+        models::{Class, Runner, UserAssignment, Verdict},
+        sandbox::SandboxOutput,
+        testing,
+    };
+
+    fn output() -> SandboxOutput {
+        SandboxOutput {
+            exit_code: Some(0),
+            stdout: b"42".to_vec(),
+            stderr: Vec::new(),
+            usage: ResourceUsage::default(),
+            config: None,
+            sandbox_error: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn run_finishing_after_cancel_keeps_cancelled() {
+        let db = testing::db().await;
+        let student = testing::user(&db, "student").await;
+        let assignment_id = testing::assignment(&db).await;
+        let test_id = testing::test(&db, assignment_id, 5).await;
+        let attempt = testing::attempt(&db, assignment_id, &student).await;
+        let runner = testing::runner(&db, &attempt, test_id).await;
+        let runner_id = runner.id;
+
+        Runner::cancel_unfinished(&db, attempt.id)
+            .await
+            .expect("runner cancelled");
+
+        let recorded = runner
+            .update_completed(&db, output(), None, None, Verdict::Accepted, 5)
+            .await
+            .expect("update ran");
+
+        let runner = Runner::by_id(&db, runner_id).await.expect("runner exists");
+
+        assert!(!recorded);
+        assert_eq!(runner.verdict, Some(Verdict::Cancelled));
+        assert_eq!(runner.points, 0);
+    }
+
+    #[tokio::test]
+    async fn unfinished_rejudge_keeps_previous_points() {
+        let db = testing::db().await;
+        let teacher = testing::user(&db, "teacher").await;
+        let student = testing::user(&db, "student").await;
+        let class_id = testing::class(&db, &teacher).await;
+        let assignment_id = testing::assignment(&db).await;
+        let test_id = testing::test(&db, assignment_id, 5).await;
+        testing::assign(&db, class_id, assignment_id, &student).await;
+        let attempt = testing::attempt(&db, assignment_id, &student).await;
+
+        // every way of counting the student's points agrees
+        let points = async |db: &SqlitePool| {
+            let attempt = Runner::scored_points(db, attempt.id)
+                .await
+                .expect("attempt points");
+            let class = Class::points_for_student(db, class_id, student.id)
+                .await
+                .expect("class points");
+            let assignments =
+                UserAssignment::assignments_for_user_with_points(db, student.id, class_id)
+                    .await
+                    .expect("assignment points");
+
+            assert_eq!((class.achieved(), class.maximum()), (attempt, 5));
+            assert!(
+                assignments
+                    .iter()
+                    .all(|(_, points)| (points.achieved(), points.maximum()) == (attempt, 5))
+            );
+
+            attempt
+        };
+
+        testing::runner(&db, &attempt, test_id)
+            .await
+            .update_completed(&db, output(), None, None, Verdict::Accepted, 5)
+            .await
+            .expect("first run recorded");
+
+        assert_eq!(points(&db).await, 5);
+
+        testing::runner(&db, &attempt, test_id).await;
+
+        assert_eq!(points(&db).await, 5, "queued rejudge");
+
+        Runner::cancel_unfinished(&db, attempt.id)
+            .await
+            .expect("rejudge cancelled");
+
+        assert_eq!(points(&db).await, 5, "cancelled rejudge");
+
+        let failed = testing::runner(&db, &attempt, test_id).await;
+        Runner::finish_system_error(&db, failed.id, "sandbox failed")
+            .await
+            .expect("rejudge failed");
+
+        assert_eq!(points(&db).await, 5, "failed rejudge");
+
+        testing::runner(&db, &attempt, test_id)
+            .await
+            .update_completed(&db, output(), None, None, Verdict::WrongAnswer, 0)
+            .await
+            .expect("rejudge recorded");
+
+        assert_eq!(points(&db).await, 0, "finished rejudge");
     }
 }
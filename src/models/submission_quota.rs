@@ -0,0 +1,172 @@
+use chrono::{NaiveDateTime, TimeDelta};
+use sqlx::SqliteExecutor;
+use uuid::Uuid;
+
+/// Window the daily quota of an assignment is counted over.
+const DAY: TimeDelta = TimeDelta::hours(24);
+
+/// How many more attempts a user may submit for an assignment, and when.
+///
+/// The limits are set per assignment and every one of them is optional.
+#[derive(Clone, Copy, Debug)]
+pub struct SubmissionQuota {
+    pub max_attempts: Option<i64>,
+    pub cooldown_seconds: Option<i64>,
+    pub daily_quota: Option<i64>,
+
+    /// Attempts the user submitted so far
+    pub attempts: i64,
+    /// Attempts the user submitted within the last 24 hours
+    pub attempts_last_day: i64,
+    pub last_submitted_at: Option<NaiveDateTime>,
+}
+
+impl SubmissionQuota {
+    pub async fn for_user(
+        db: impl SqliteExecutor<'_>,
+        assignment_id: Uuid,
+        user_id: Uuid,
+        now: NaiveDateTime,
+    ) -> sqlx::Result<Self> {
+        let day_ago = now - DAY;
+
+        sqlx::query_as!(
+            SubmissionQuota,
+            r#"SELECT
+                a.max_attempts,
+                a.cooldown_seconds,
+                a.daily_quota,
+                (SELECT count(*) FROM attempts WHERE assignment_id = a.id AND user_id = ?2) as "attempts!: i64",
+                (SELECT count(*) FROM attempts WHERE assignment_id = a.id AND user_id = ?2 AND submitted_at > ?3) as "attempts_last_day!: i64",
+                (SELECT max(submitted_at) FROM attempts WHERE assignment_id = a.id AND user_id = ?2) as "last_submitted_at: NaiveDateTime"
+            FROM assignments a
+            WHERE a.id = ?1"#,
+            assignment_id,
+            user_id,
+            day_ago,
+        )
+        .fetch_one(db)
+        .await
+    }
+
+    /// Attempts left in total, `None` if the assignment has no cap.
+    pub fn remaining(&self) -> Option<i64> {
+        self.max_attempts
+            .map(|max| max.saturating_sub(self.attempts).max(0))
+    }
+
+    /// Attempts left within the last 24 hours, `None` if the assignment has no daily quota.
+    pub fn remaining_today(&self) -> Option<i64> {
+        self.daily_quota
+            .map(|quota| quota.saturating_sub(self.attempts_last_day).max(0))
+    }
+
+    /// Checks whether another attempt may be submitted at `now`, with the reason if not.
+    pub fn check(&self, now: NaiveDateTime) -> Result<(), String> {
+        if let Some(max) = self.max_attempts
+            && self.attempts >= max
+        {
+            return Err(format!(
+                "You have used all {max} attempts for this assignment."
+            ));
+        }
+
+        if let (Some(cooldown), Some(last)) = (self.cooldown_seconds, self.last_submitted_at) {
+            let wait = (last + TimeDelta::seconds(cooldown) - now).num_seconds();
+
+            if wait > 0 {
+                return Err(format!(
+                    "Please wait {wait} more seconds before submitting again."
+                ));
+            }
+        }
+
+        if let Some(quota) = self.daily_quota
+            && self.attempts_last_day >= quota
+        {
+            return Err(format!(
+                "You have used all {quota} attempts allowed within 24 hours, try again later."
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDateTime, TimeDelta};
+
+    use super::SubmissionQuota;
+
+    fn now() -> NaiveDateTime {
+        NaiveDateTime::parse_from_str("2026-02-22 12:00:00", "%Y-%m-%d %H:%M:%S")
+            .unwrap_or_default()
+    }
+
+    fn quota() -> SubmissionQuota {
+        SubmissionQuota {
+            max_attempts: None,
+            cooldown_seconds: None,
+            daily_quota: None,
+            attempts: 3,
+            attempts_last_day: 2,
+            last_submitted_at: Some(now() - TimeDelta::seconds(30)),
+        }
+    }
+
+    #[test]
+    fn unlimited_assignment_accepts() {
+        assert!(quota().check(now()).is_ok());
+    }
+
+    #[test]
+    fn cap_counts_every_attempt() {
+        let below = SubmissionQuota {
+            max_attempts: Some(4),
+            ..quota()
+        };
+        let reached = SubmissionQuota {
+            max_attempts: Some(3),
+            ..quota()
+        };
+
+        assert!(below.check(now()).is_ok());
+        assert!(reached.check(now()).is_err());
+        assert_eq!(reached.remaining(), Some(0));
+    }
+
+    #[test]
+    fn cooldown_runs_from_last_attempt() {
+        let waiting = SubmissionQuota {
+            cooldown_seconds: Some(60),
+            ..quota()
+        };
+        let over = SubmissionQuota {
+            cooldown_seconds: Some(30),
+            ..quota()
+        };
+
+        assert_eq!(
+            waiting.check(now()),
+            Err("Please wait 30 more seconds before submitting again.".to_owned())
+        );
+        assert!(over.check(now()).is_ok());
+    }
+
+    #[test]
+    fn daily_quota_counts_last_day_only() {
+        let below = SubmissionQuota {
+            daily_quota: Some(3),
+            ..quota()
+        };
+        let reached = SubmissionQuota {
+            daily_quota: Some(2),
+            ..quota()
+        };
+
+        assert!(below.check(now()).is_ok());
+        assert!(reached.check(now()).is_err());
+        assert_eq!(below.remaining_today(), Some(1));
+    }
+}
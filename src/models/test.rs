@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
 use uuid::Uuid;
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -16,6 +17,42 @@ pub struct Test {
     pub assignment_id: Uuid,
 }
 
+impl Test {
+    pub async fn by_id(db: &SqlitePool, id: Uuid) -> sqlx::Result<Self> {
+        sqlx::query_as!(
+            Test,
+            r#"SELECT
+                id as "id: uuid::Uuid",
+                name,
+                description,
+                type as "type_: TestType",
+                assignment_id as "assignment_id: uuid::Uuid",
+                points
+            FROM tests WHERE id = ?"#,
+            id
+        )
+        .fetch_one(db)
+        .await
+    }
+
+    pub async fn for_assignment(db: &SqlitePool, assignment_id: Uuid) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Test,
+            r#"SELECT
+                id as "id: uuid::Uuid",
+                name,
+                description,
+                type as "type_: TestType",
+                assignment_id as "assignment_id: uuid::Uuid",
+                points
+            FROM tests WHERE assignment_id = ?"#,
+            assignment_id
+        )
+        .fetch_all(db)
+        .await
+    }
+}
+
 #[derive(sqlx::Type, Serialize, Deserialize, Debug)]
 #[sqlx(rename_all = "snake_case")]
 pub enum TestType {
@@ -5,10 +5,14 @@ use uuid::Uuid;
 #[derive(Debug, Deserialize, Serialize)]
 pub struct User {
     pub id: Uuid,
-    pub google_sub: String,
-
     pub email: String,
     pub name: String,
+
+    /// Administrators act as teachers of every class
+    pub is_admin: bool,
+
+    /// University ID, known once the student was imported from a roster
+    pub student_id: Option<String>,
 }
 
 impl User {
@@ -17,10 +21,12 @@ impl User {
             User,
             r#"SELECT
             id as "id: Uuid",
-            google_sub,
             email,
-            name
+            name,
+            is_admin,
+            student_id
             FROM users
+            ORDER BY name
             "#
         )
         .fetch_all(db)
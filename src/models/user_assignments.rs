@@ -19,7 +19,7 @@ impl UserAssignment {
                 (SELECT COALESCE(SUM(t.points), 0) FROM tests t WHERE t.assignment_id = a.id) as "max_points!: i64",
                 (
                     SELECT COALESCE(SUM(r.points), 0)
-                    FROM runners r
+                    FROM scored_runners r
                     WHERE r.attempt_id = (
                         SELECT att.id
                         FROM attempts att
@@ -0,0 +1,200 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Final classification of a single test run.
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    Accepted,
+    WrongAnswer,
+    TimeLimitExceeded,
+    MemoryLimitExceeded,
+    RuntimeError,
+    CompileError,
+    SystemError,
+    /// Stopped before finishing, because the attempt was superseded or cancelled by a teacher
+    Cancelled,
+}
+
+/// nsjail reports a jailed process killed by a signal as `128 + signal`.
+const SIGNAL_EXIT_BASE: i32 = 128;
+
+const SIGXCPU: i32 = 24;
+
+impl Verdict {
+    pub const ALL: [Self; 8] = [
+        Self::Accepted,
+        Self::WrongAnswer,
+        Self::TimeLimitExceeded,
+        Self::MemoryLimitExceeded,
+        Self::RuntimeError,
+        Self::CompileError,
+        Self::SystemError,
+        Self::Cancelled,
+    ];
+
+    /// Classifies a finished run.
+    ///
+    /// `exit_code` is the status the program exited with, `None` if the sandbox failed to run
+    /// it or was killed, see
+    /// [`SandboxOutput::program_exit_code`](crate::sandbox::SandboxOutput::program_exit_code).
+    /// `oom_killed` is whether the cgroup of the run saw an out-of-memory kill.
+    /// `output_matches` is only consulted when the program exited cleanly.
+    #[must_use]
+    pub fn classify(
+        exit_code: Option<i32>,
+        elapsed: Duration,
+        time_limit: i64,
+        oom_killed: bool,
+        output_matches: bool,
+    ) -> Self {
+        let Some(code) = exit_code else {
+            return Self::SystemError;
+        };
+
+        let timed_out = u64::try_from(time_limit).is_ok_and(|limit| elapsed.as_secs() >= limit);
+
+        match code {
+            _ if oom_killed => Self::MemoryLimitExceeded,
+            _ if timed_out => Self::TimeLimitExceeded,
+            code if code > SIGNAL_EXIT_BASE => match code - SIGNAL_EXIT_BASE {
+                SIGXCPU => Self::TimeLimitExceeded,
+                _ => Self::RuntimeError,
+            },
+            0 if output_matches => Self::Accepted,
+            0 => Self::WrongAnswer,
+            _ => Self::RuntimeError,
+        }
+    }
+
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Accepted => "accepted",
+            Self::WrongAnswer => "wrong_answer",
+            Self::TimeLimitExceeded => "time_limit_exceeded",
+            Self::MemoryLimitExceeded => "memory_limit_exceeded",
+            Self::RuntimeError => "runtime_error",
+            Self::CompileError => "compile_error",
+            Self::SystemError => "system_error",
+            Self::Cancelled => "cancelled",
+        }
+    }
+
+    #[must_use]
+    pub const fn abbreviation(self) -> &'static str {
+        match self {
+            Self::Accepted => "AC",
+            Self::WrongAnswer => "WA",
+            Self::TimeLimitExceeded => "TLE",
+            Self::MemoryLimitExceeded => "MLE",
+            Self::RuntimeError => "RE",
+            Self::CompileError => "CE",
+            Self::SystemError => "SE",
+            Self::Cancelled => "CA",
+        }
+    }
+
+    #[must_use]
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Accepted => "Accepted",
+            Self::WrongAnswer => "Wrong answer",
+            Self::TimeLimitExceeded => "Time limit exceeded",
+            Self::MemoryLimitExceeded => "Memory limit exceeded",
+            Self::RuntimeError => "Runtime error",
+            Self::CompileError => "Compile error",
+            Self::SystemError => "System error",
+            Self::Cancelled => "Cancelled",
+        }
+    }
+
+    #[must_use]
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|verdict| verdict.as_str() == value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Verdict;
+
+    const LIMIT: i64 = 2;
+
+    fn classify(
+        exit_code: Option<i32>,
+        elapsed_ms: u64,
+        oom_killed: bool,
+        matches: bool,
+    ) -> Verdict {
+        Verdict::classify(
+            exit_code,
+            Duration::from_millis(elapsed_ms),
+            LIMIT,
+            oom_killed,
+            matches,
+        )
+    }
+
+    #[test]
+    fn failed_sandbox_is_system_error() {
+        assert_eq!(classify(None, 100, false, true), Verdict::SystemError);
+    }
+
+    #[test]
+    fn exit_255_is_runtime_error() {
+        assert_eq!(classify(Some(255), 100, false, true), Verdict::RuntimeError);
+    }
+
+    #[test]
+    fn signals_are_runtime_errors() {
+        // SIGSEGV
+        assert_eq!(
+            classify(Some(128 + 11), 100, false, true),
+            Verdict::RuntimeError
+        );
+        // SIGKILL
+        assert_eq!(
+            classify(Some(128 + 9), 100, false, true),
+            Verdict::RuntimeError
+        );
+    }
+
+    #[test]
+    fn sigxcpu_is_time_limit_exceeded() {
+        assert_eq!(
+            classify(Some(128 + 24), 100, false, false),
+            Verdict::TimeLimitExceeded
+        );
+    }
+
+    #[test]
+    fn oom_wins_over_timeout() {
+        assert_eq!(
+            classify(Some(128 + 9), 2500, true, false),
+            Verdict::MemoryLimitExceeded
+        );
+    }
+
+    #[test]
+    fn timeout_starts_at_the_limit() {
+        assert_eq!(classify(Some(0), 1999, false, true), Verdict::Accepted);
+        assert_eq!(
+            classify(Some(0), 2000, false, true),
+            Verdict::TimeLimitExceeded
+        );
+    }
+
+    #[test]
+    fn clean_exit_is_judged_by_output() {
+        assert_eq!(classify(Some(0), 100, false, true), Verdict::Accepted);
+        assert_eq!(classify(Some(0), 100, false, false), Verdict::WrongAnswer);
+        assert_eq!(classify(Some(1), 100, false, true), Verdict::RuntimeError);
+    }
+}
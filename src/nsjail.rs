@@ -1,238 +1,608 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use chrono::Utc;
+use clap::ValueEnum;
 use sqlx::SqlitePool;
 use tokio::fs;
-use tokio::io::AsyncWriteExt;
-use tokio::{process::Command, task::JoinHandle};
+use tokio::process::Command;
 use uuid::Uuid;
 
-use crate::models::{Runner, Test, TestType};
+use crate::cgroup::RunCgroup;
+use crate::models::{Compilation, NetworkPolicy, Runner, Test, TestType, Verdict};
+use crate::sandbox::{
+    CancelSignal, Sandbox, SandboxFuture, SandboxOutput, communicate, pinned_command,
+};
+
+/// Directory mounted at `/workspace/build`; only the compile step gets it writable.
+#[derive(Debug)]
+pub enum BuildMount {
+    ReadOnly(PathBuf),
+    Writable(PathBuf),
+}
 
+#[derive(Debug)]
 pub struct NSJailBlueprint {
     pub tests: PathBuf,
 
     pub memory_limit: i64,
     pub time_limit: i64,
     pub max_cpus: i64,
-    pub disable_network: bool,
+    pub network: NetworkPolicy,
+    /// Read-only root of the jail, nothing from the host filesystem is visible besides it
+    pub rootfs: PathBuf,
     pub mountpoint: PathBuf,
+    pub build_dir: Option<BuildMount>,
 
     pub command: String,
     pub write_stdin: bool,
 
-    pub quiet: bool,
+    /// CPU the whole sandbox is pinned to, set by the judge slot that runs it
+    pub cpu: Option<usize>,
 }
 
 impl NSJailBlueprint {
-    pub fn into_command(self) -> Command {
-        let mut cmd = Command::new("sudo");
+    /// Runner row recording the blueprint as a queued test of the attempt.
+    pub fn pending_runner(&self, test_id: Uuid, attempt_id: Uuid) -> Runner {
+        Runner {
+            id: Uuid::new_v4(),
+            test_id,
+            attempt_id,
+            passed: false,
+            points: 0,
+            verdict: None,
+            command_ran: self.command.clone(),
+            user_command_ran: self.command.clone(),
+            created_at: Utc::now().naive_utc(),
+            started_at: None,
+            finished_at: None,
+            exit_code: None,
+            stdout: None,
+            stderr: None,
+            expected_stderr: None,
+            expected_stdout: None,
+            memory_limit: self.memory_limit,
+            time_limit: self.time_limit,
+            max_cpus: self.max_cpus,
+            network_policy: self.network,
+            wall_time_ms: None,
+            cpu_time_ms: None,
+            peak_memory_kb: None,
+            sandbox_config: None,
+            error_reason: None,
+        }
+    }
 
-        cmd.stdout(Stdio::piped());
-        cmd.stderr(Stdio::piped());
+    /// Compilation row recording the blueprint as the queued build of the attempt.
+    pub fn pending_compilation(&self, attempt_id: Uuid) -> Compilation {
+        Compilation {
+            id: Uuid::new_v4(),
+            attempt_id,
+            succeeded: false,
+            outcome: None,
+            command_ran: self.command.clone(),
+            created_at: Utc::now().naive_utc(),
+            started_at: None,
+            finished_at: None,
+            exit_code: None,
+            stdout: None,
+            stderr: None,
+            memory_limit: self.memory_limit,
+            time_limit: self.time_limit,
+            max_cpus: self.max_cpus,
+            network_policy: self.network,
+            sandbox_config: None,
+            error_reason: None,
+        }
+    }
 
-        if self.write_stdin {
-            cmd.stdin(Stdio::piped());
+    /// Renders the blueprint as an nsjail protobuf-text config.
+    pub fn to_config(&self, cgroup: &Path, pids_limit: i64, user_namespace: bool) -> String {
+        let mut config = String::new();
+
+        let mut line = |key: &str, value: String| {
+            config.push_str(key);
+            config.push_str(": ");
+            config.push_str(&value);
+            config.push('\n');
+        };
+
+        line("name", quote("evaltor"));
+        line("mode", "ONCE".to_owned());
+        line("hostname", quote("evaltor"));
+        line("cwd", quote("/workspace"));
+        line("time_limit", self.time_limit.to_string());
+        line("max_cpus", self.max_cpus.to_string());
+        line("keep_env", "false".to_owned());
+        line("envar", quote("PATH=/usr/local/bin:/usr/bin:/bin"));
+
+        // address space limits break runtimes that reserve large heaps up front (JVM, Go), so
+        // memory is enforced by the cgroup instead
+        line("rlimit_as_type", "INF".to_owned());
+        line("use_cgroupv2", "true".to_owned());
+        line("cgroupv2_mount", quote(&cgroup.to_string_lossy()));
+        line(
+            "cgroup_mem_max",
+            self.memory_limit.saturating_mul(1024 * 1024).to_string(),
+        );
+        line("cgroup_mem_swap_max", "0".to_owned());
+        line("cgroup_pids_max", pids_limit.to_string());
+        line(
+            "cgroup_cpu_ms_per_sec",
+            self.max_cpus.saturating_mul(1000).to_string(),
+        );
+
+        line("clone_newuser", user_namespace.to_string());
+        for (key, value) in network_options(self.network) {
+            line(key, (*value).to_owned());
         }
 
-        cmd.args([
-            "nsjail",
-            "--mode",
-            "o",
-            "--chroot",
-            "/",
-            "--cwd",
-            "/workspace",
-            "--tmpfsmount",
-            "/tmp",
-            "--disable_clone_newuser",
-        ]);
-
-        cmd.arg("--rlimit_as").arg(self.memory_limit.to_string());
-        cmd.arg("--time_limit").arg(self.time_limit.to_string());
-        cmd.arg("--max_cpus").arg(self.max_cpus.to_string());
-        cmd.arg("--bindmount_ro")
-            .arg(format!("{}:/workspace", self.mountpoint.to_string_lossy()));
-
-        if self.disable_network {
-            cmd.arg("--disable_clone_newnet");
+        line("mount_proc", "true".to_owned());
+        line("mount", mount(&self.rootfs.to_string_lossy(), "/", false));
+        line(
+            "mount",
+            mount(&self.mountpoint.to_string_lossy(), "/workspace", false),
+        );
+
+        match &self.build_dir {
+            Some(BuildMount::ReadOnly(path)) => {
+                line(
+                    "mount",
+                    mount(&path.to_string_lossy(), "/workspace/build", false),
+                );
+            }
+            Some(BuildMount::Writable(path)) => {
+                line(
+                    "mount",
+                    mount(&path.to_string_lossy(), "/workspace/build", true),
+                );
+            }
+            None => {}
         }
 
-        if self.quiet {
-            cmd.arg("--quiet");
+        line(
+            "mount",
+            format!("{{ dst: {} fstype: \"tmpfs\" rw: true }}", quote("/tmp")),
+        );
+
+        for device in ["/dev/null", "/dev/zero", "/dev/urandom"] {
+            line("mount", mount(device, device, true));
         }
 
-        cmd.arg("--").args(self.command.split_ascii_whitespace());
+        line("seccomp_string", quote(SECCOMP_POLICY));
+
+        let mut command = self.command.split_ascii_whitespace();
+
+        let mut exec_bin = format!("{{ path: {}", quote(command.next().unwrap_or_default()));
+        for arg in command {
+            exec_bin.push_str(" arg: ");
+            exec_bin.push_str(&quote(arg));
+        }
+        exec_bin.push_str(" }");
+
+        line("exec_bin", exec_bin);
+
+        config
+    }
+}
+
+/// nsjail config options implementing the policy.
+///
+/// Note that nsjail brings up loopback in a new network namespace unless told otherwise.
+const fn network_options(policy: NetworkPolicy) -> &'static [(&'static str, &'static str)] {
+    match policy {
+        NetworkPolicy::Isolated => &[("clone_newnet", "true"), ("iface_no_lo", "true")],
+        NetworkPolicy::Loopback => &[("clone_newnet", "true"), ("iface_no_lo", "false")],
+        NetworkPolicy::Host => &[("clone_newnet", "false")],
+    }
+}
+
+/// Syscalls no submission has a reason to make, everything else is allowed.
+const SECCOMP_POLICY: &str = "POLICY evaltor { KILL { ptrace, process_vm_readv, process_vm_writev, mount, umount2, pivot_root, chroot, unshare, setns, kexec_load, init_module, finit_module, delete_module, reboot, swapon, swapoff, bpf, perf_event_open, keyctl, add_key, request_key } } USE evaltor DEFAULT ALLOW";
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn mount(src: &str, dst: &str, rw: bool) -> String {
+    format!(
+        "{{ src: {} dst: {} is_bind: true rw: {rw} }}",
+        quote(src),
+        quote(dst)
+    )
+}
+
+/// How nsjail gets the privileges it needs to set up the jail.
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum NsjailPrivilege {
+    /// Run nsjail through `sudo`
+    #[default]
+    Sudo,
+    /// The server itself runs as root, run nsjail directly
+    Root,
+    /// Run nsjail unprivileged inside a new user namespace
+    Rootless,
+}
+
+/// Runs blueprints through nsjail, each in its own cgroup and with its own config file.
+#[derive(Debug)]
+pub struct NsjailSandbox {
+    pub nsjail_path: PathBuf,
+    pub privilege: NsjailPrivilege,
+    pub cgroup_root: PathBuf,
+    pub pids_limit: i64,
+}
+
+impl NsjailSandbox {
+    fn command(&self, blueprint: &NSJailBlueprint, config_path: &Path, log_path: &Path) -> Command {
+        let mut cmd = match self.privilege {
+            NsjailPrivilege::Sudo => {
+                let mut cmd = pinned_command("sudo", blueprint.cpu);
+                cmd.arg(&self.nsjail_path);
+                cmd
+            }
+            NsjailPrivilege::Root | NsjailPrivilege::Rootless => {
+                pinned_command(&self.nsjail_path, blueprint.cpu)
+            }
+        };
+
+        cmd.stdout(Stdio::piped());
+        cmd.stderr(Stdio::piped());
+
+        if blueprint.write_stdin {
+            cmd.stdin(Stdio::piped());
+        }
+
+        // nsjail logs to its own file, so the program's stderr is only the program's
+        cmd.arg("--config")
+            .arg(config_path)
+            .arg("--log")
+            .arg(log_path);
 
         cmd
     }
 }
 
+impl Sandbox for NsjailSandbox {
+    fn run(
+        &self,
+        blueprint: NSJailBlueprint,
+        run_id: Uuid,
+        stdin: Vec<u8>,
+        cancel: CancelSignal,
+    ) -> SandboxFuture<'_> {
+        Box::pin(async move {
+            let cgroup = RunCgroup::create(&self.cgroup_root, run_id).await?;
+
+            let config = blueprint.to_config(
+                cgroup.path(),
+                self.pids_limit,
+                matches!(self.privilege, NsjailPrivilege::Rootless),
+            );
+
+            // nsjail run through sudo writes its log as root: into a file the server created
+            // in a directory it owns, so the server can still read and delete it afterwards
+            let run_dir = std::env::temp_dir().join(format!("evaltor-{run_id}"));
+            let config_path = run_dir.join("nsjail.cfg");
+            let log_path = run_dir.join("nsjail.log");
+
+            let prepared = async {
+                fs::DirBuilder::new().mode(0o700).create(&run_dir).await?;
+                fs::write(&config_path, &config).await?;
+                fs::write(&log_path, "").await
+            }
+            .await;
+
+            if let Err(err) = prepared {
+                cgroup.finish(Duration::ZERO).await;
+                remove_run_dir(&run_dir).await;
+                return Err(err);
+            }
+
+            let cmd = self.command(&blueprint, &config_path, &log_path);
+
+            let started = Instant::now();
+
+            let output = communicate(cmd, &stdin, cancel).await;
+
+            let usage = cgroup.finish(started.elapsed()).await;
+
+            let sandbox_error = match fs::read_to_string(&log_path).await {
+                Ok(log) => jail_failure(&log),
+                Err(err) => Some(format!("failed to read the nsjail log: {err}")),
+            };
+
+            remove_run_dir(&run_dir).await;
+
+            let output = output?;
+
+            Ok(SandboxOutput {
+                exit_code: output.status.code(),
+                stdout: output.stdout,
+                stderr: output.stderr,
+                usage,
+                config: Some(config),
+                sandbox_error,
+            })
+        })
+    }
+}
+
+async fn remove_run_dir(run_dir: &Path) {
+    if let Err(err) = fs::remove_dir_all(run_dir).await {
+        eprintln!("Failed to remove {}: {err}", run_dir.display());
+    }
+}
+
+/// The last line nsjail logged if it never reported how the jailed program ended, i.e. it
+/// failed to set up or supervise the jail rather than the program failing.
+fn jail_failure(log: &str) -> Option<String> {
+    let child_status_logged = log.lines().any(|line| {
+        line.contains(") exited with status: ") || line.contains(") terminated with signal: ")
+    });
+
+    if child_status_logged {
+        return None;
+    }
+
+    Some(
+        log.trim()
+            .lines()
+            .last()
+            .unwrap_or("nsjail did not log anything")
+            .to_owned(),
+    )
+}
+
 pub struct Instance {
     blueprint: NSJailBlueprint,
+    sandbox: Arc<dyn Sandbox>,
+    cancel: CancelSignal,
+}
+
+/// Describes a run the sandbox itself failed.
+fn sandbox_failure(output: &SandboxOutput) -> String {
+    match (&output.sandbox_error, output.exit_code) {
+        (Some(error), Some(code)) => format!("sandbox exited with {code}: {error}"),
+        (Some(error), None) => format!("sandbox was killed: {error}"),
+        (None, _) => "sandbox was killed".to_owned(),
+    }
 }
 
 impl Instance {
-    pub const fn new(blueprint: NSJailBlueprint) -> Self {
-        Self { blueprint }
+    pub fn new(
+        blueprint: NSJailBlueprint,
+        sandbox: Arc<dyn Sandbox>,
+        cancel: CancelSignal,
+    ) -> Self {
+        Self {
+            blueprint,
+            sandbox,
+            cancel,
+        }
     }
 
-    pub fn spawn(self, db: SqlitePool, test_id: Uuid, attempt_id: Uuid) -> JoinHandle<()> {
-        tokio::spawn(Self::worker(self.blueprint, db, test_id, attempt_id))
+    /// Runs the blueprint as the started `compilation` and returns whether it succeeded.
+    ///
+    /// Fails with the reason if the build could not be run at all.
+    pub async fn compile(self, db: &SqlitePool, compilation: Compilation) -> Result<bool, String> {
+        let output = self
+            .sandbox
+            .run(self.blueprint, compilation.id, Vec::new(), self.cancel)
+            .await
+            .map_err(|err| format!("failed to run sandbox: {err}"))?;
+
+        record_compilation(db, compilation, output).await
     }
 
-    #[expect(clippy::too_many_lines)]
-    async fn worker(
-        blueprint: NSJailBlueprint,
-        db: SqlitePool,
-        test_id: Uuid,
-        attempt_id: Uuid,
-    ) -> () {
-        let id = Uuid::new_v4();
-
-        let runner = Runner {
-            id,
-            test_id,
-            attempt_id,
-            passed: false,
-            points: 0,
-            command_ran: blueprint.command.clone(),
-            user_command_ran: blueprint.command.clone(),
-            created_at: Utc::now().naive_utc(),
-            finished_at: None,
-            exit_code: None,
-            stdout: None,
-            stderr: None,
-            expected_stderr: None,
-            expected_stdout: None,
-            memory_limit: blueprint.memory_limit,
-            time_limit: blueprint.time_limit,
-            max_cpus: blueprint.max_cpus,
-            disable_network: blueprint.disable_network,
+    /// Runs the blueprint as the started test `runner` and records its verdict.
+    ///
+    /// Fails with the reason if the test could not be judged, the runner is left untouched then.
+    pub async fn run_test(self, db: &SqlitePool, runner: Runner) -> Result<(), String> {
+        let blueprint = self.blueprint;
+
+        let tests_path = blueprint.tests.clone();
+
+        let stdin_content = if blueprint.write_stdin {
+            test_input(db, &tests_path, &runner).await?
+        } else {
+            Vec::new()
         };
 
-        if let Err(err) = runner.insert_new(&db).await {
-            eprintln!("Failed to insert runner into database for command: {err:?}");
-            return;
-        }
+        let output = self
+            .sandbox
+            .run(blueprint, runner.id, stdin_content, self.cancel)
+            .await
+            .map_err(|err| format!("failed to run sandbox: {err}"))?;
 
-        let write_stdin = blueprint.write_stdin;
-        let tests_path = blueprint.tests.clone();
+        record_test(db, &tests_path, runner, output).await
+    }
+}
 
-        let mut cmd = blueprint.into_command();
+/// Directory holding `test.in` and `test.out` of the test.
+fn test_dir(tests: &Path, test: &Test) -> PathBuf {
+    tests
+        .join(test.assignment_id.to_string())
+        .join(test.id.to_string())
+}
 
-        let Ok(mut child) = cmd.spawn() else {
-            eprintln!("Failed to spawn nsjail for command");
-            return;
-        };
+/// Standard input the runner's test feeds the program, empty if the test has none.
+pub async fn test_input(db: &SqlitePool, tests: &Path, runner: &Runner) -> Result<Vec<u8>, String> {
+    let test = Test::by_id(db, runner.test_id)
+        .await
+        .map_err(|err| format!("failed to fetch test: {err}"))?;
 
-        let Ok(test) = sqlx::query_as!(
-            Test,
-            r#"SELECT
-                id as "id: uuid::Uuid",
-                assignment_id as "assignment_id: uuid::Uuid",
-                type as "type_: TestType",
-                name,
-                description,
-                points
-            FROM tests WHERE id = ?"#,
-            test_id
-        )
-        .fetch_one(&db)
+    Ok(fs::read(test_dir(tests, &test).join("test.in"))
         .await
-        else {
-            return;
-        };
+        .unwrap_or_default())
+}
 
-        if write_stdin {
-            let stdin_path = tests_path
-                .join(test.assignment_id.to_string())
-                .join(test.id.to_string())
-                .join("test.in");
+/// Classifies the output of the `compilation`, wherever it ran, and records it.
+///
+/// Returns whether the build succeeded, fails with the reason if the sandbox itself failed.
+pub async fn record_compilation(
+    db: &SqlitePool,
+    compilation: Compilation,
+    output: SandboxOutput,
+) -> Result<bool, String> {
+    let verdict = Verdict::classify(
+        output.program_exit_code(),
+        output.usage.wall_time,
+        compilation.time_limit,
+        output.usage.oom_killed,
+        true,
+    );
+
+    if verdict == Verdict::SystemError {
+        return Err(sandbox_failure(&output));
+    }
 
-            let stdin_content = fs::read(&stdin_path).await.unwrap_or_default();
+    let succeeded = output.exit_code == Some(0);
 
-            if let Some(mut stdin) = child.stdin.take() {
-                _ = stdin.write_all(&stdin_content).await;
-            }
-        }
+    // a compilation cancelled while it ran stays cancelled, and so do its tests
+    let recorded = compilation
+        .update_completed(db, output, succeeded)
+        .await
+        .map_err(|err| format!("failed to update compilation: {err}"))?;
 
-        let Ok(output) = child.wait_with_output().await else {
-            eprintln!("Failed to wait for nsjail output for command",);
-            return;
-        };
+    Ok(succeeded && recorded)
+}
 
-        let expected_stdout;
+/// Judges the output of the test `runner`, wherever it ran, and records its verdict.
+///
+/// Fails with the reason if the test could not be judged, the runner is left untouched then.
+pub async fn record_test(
+    db: &SqlitePool,
+    tests: &Path,
+    runner: Runner,
+    output: SandboxOutput,
+) -> Result<(), String> {
+    let test = Test::by_id(db, runner.test_id)
+        .await
+        .map_err(|err| format!("failed to fetch test: {err}"))?;
 
-        let passed = match test.type_ {
-            TestType::Compare => {
-                let expected_output = tests_path
-                    .join(test.assignment_id.to_string())
-                    .join(test.id.to_string())
-                    .join("test.out");
+    let expected_stdout;
 
-                let Ok(expected_output) = fs::read(&expected_output).await else {
-                    eprintln!("Failed to read expected output file at");
-                    return;
-                };
+    let output_matches = match test.type_ {
+        TestType::Compare => {
+            let expected_path = test_dir(tests, &test).join("test.out");
 
-                let success = expected_output.trim_ascii() == output.stdout.trim_ascii();
+            let expected_output = fs::read(&expected_path).await.map_err(|err| {
+                format!(
+                    "failed to read expected output {}: {err}",
+                    expected_path.display()
+                )
+            })?;
 
-                expected_stdout = Some(expected_output);
+            let success = expected_output.trim_ascii() == output.stdout.trim_ascii();
 
-                success
-            }
-        };
+            expected_stdout = Some(expected_output);
+
+            success
+        }
+    };
+
+    let verdict = Verdict::classify(
+        output.program_exit_code(),
+        output.usage.wall_time,
+        runner.time_limit,
+        output.usage.oom_killed,
+        output_matches,
+    );
+
+    if verdict == Verdict::SystemError {
+        return Err(sandbox_failure(&output));
+    }
 
-        let Ok(runner) = sqlx::query_as!(
-            Runner,
-            r#"SELECT
-                id as "id: uuid::Uuid",
-                test_id as "test_id: uuid::Uuid",
-                attempt_id as "attempt_id: uuid::Uuid",
-                passed,
-                points,
-                command_ran,
-                user_command_ran,
-                created_at as "created_at: chrono::NaiveDateTime",
-                finished_at as "finished_at: chrono::NaiveDateTime",
-                exit_code,
-                stdout,
-                stderr,
-                expected_stdout,
-                expected_stderr,
-                memory_limit,
-                time_limit,
-                max_cpus,
-                disable_network
-            FROM runners WHERE id = ?"#,
-            id
-        )
-        .fetch_one(&db)
+    let points = if verdict == Verdict::Accepted {
+        test.points
+    } else {
+        0
+    };
+
+    let runner_id = runner.id;
+
+    // a runner cancelled while it ran keeps its verdict
+    if !runner
+        .update_completed(db, output, expected_stdout, None, verdict, points)
         .await
-        else {
-            eprintln!("Failed to fetch runner from database");
-            return;
-        };
+        .map_err(|err| format!("failed to update runner: {err}"))?
+    {
+        eprintln!("Runner {runner_id} was finished meanwhile, its run is not recorded");
+    }
 
-        let points = if passed { test.points } else { 0 };
-
-        if let Err(err) = runner
-            .update_completed(
-                &db,
-                output.status.code(),
-                output.stdout,
-                output.stderr,
-                expected_stdout,
-                None,
-                passed,
-                points,
-            )
-            .await
-        {
-            eprintln!("Failed to update runner: {err:?}");
-            return;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{BuildMount, NSJailBlueprint, jail_failure};
+    use crate::models::NetworkPolicy;
+
+    fn config(network: NetworkPolicy) -> String {
+        NSJailBlueprint {
+            tests: "/srv/tests".into(),
+            memory_limit: 256,
+            time_limit: 2,
+            max_cpus: 1,
+            network,
+            rootfs: "/srv/rootfs/python".into(),
+            mountpoint: "/srv/submissions/attempt".into(),
+            build_dir: Some(BuildMount::ReadOnly(
+                "/srv/submissions/attempt/build".into(),
+            )),
+            command: "/usr/bin/python3 main.py".to_owned(),
+            write_stdin: true,
+            cpu: None,
         }
+        .to_config(Path::new("/sys/fs/cgroup/evaltor/run"), 64, false)
+    }
+
+    #[test]
+    fn isolated_network_has_no_interfaces() {
+        let config = config(NetworkPolicy::Isolated);
+
+        assert!(config.contains("clone_newnet: true\n"));
+        assert!(config.contains("iface_no_lo: true\n"));
+    }
+
+    #[test]
+    fn loopback_network_keeps_lo() {
+        let config = config(NetworkPolicy::Loopback);
+
+        assert!(config.contains("clone_newnet: true\n"));
+        assert!(config.contains("iface_no_lo: false\n"));
+    }
+
+    #[test]
+    fn host_network_shares_namespace() {
+        let config = config(NetworkPolicy::Host);
+
+        assert!(config.contains("clone_newnet: false\n"));
+        assert!(!config.contains("iface_no_lo"));
+    }
+
+    #[test]
+    fn logged_child_status_is_not_a_jail_failure() {
+        let log = "[I][2026-02-13T10:00:00+0100] Executing '/usr/bin/python3' for '[STANDALONE MODE]'\n\
+                   [I][2026-02-13T10:00:01+0100] pid=4242 ([STANDALONE MODE]) exited with status: 255, (PIDs left: 0)\n";
+
+        assert_eq!(jail_failure(log), None);
+    }
+
+    #[test]
+    fn missing_child_status_is_a_jail_failure() {
+        let log = "[E][2026-02-13T10:00:00+0100][4242] mountFs():123 mount('/srv/rootfs') failed: No such file or directory\n\
+                   [F][2026-02-13T10:00:00+0100][4242] Launching child process failed\n";
+
+        assert_eq!(
+            jail_failure(log).as_deref(),
+            Some("[F][2026-02-13T10:00:00+0100][4242] Launching child process failed")
+        );
+        assert!(jail_failure("").is_some());
     }
 }
@@ -1,22 +1,29 @@
+use std::{collections::HashMap, io};
+
 use askama::Template;
 use axum::{
     Router,
     body::Bytes,
     extract::{Path, State},
-    response::{Html, IntoResponse},
+    response::{
+        Html, IntoResponse,
+        sse::{Event, KeepAlive, Sse},
+    },
     routing::{get, post},
 };
 use axum_typed_multipart::{FieldData, TryFromMultipart, TypedMultipart};
 use reqwest::StatusCode;
 use tokio::fs;
+use tokio_stream::{Stream, StreamExt, wrappers::BroadcastStream};
 use uuid::Uuid;
 
 use crate::{
-    auth,
-    models::{Assignment, Attempt, Test, TestType},
-    nsjail::NSJailBlueprint,
+    auth, authz,
+    evaluation::{AttemptDir, AttemptPlan},
+    models::{Assignment, Attempt, Language, SubmissionQuota, User},
+    runner_manager::{RunStage, RunnerEvent},
     state::EvaltorState,
-    templates::{AssignmentPage, AttemptsPartial},
+    templates::{AssignmentPage, AttemptsPartial, ListedAttempt},
 };
 
 pub fn router() -> axum::Router<EvaltorState> {
@@ -24,6 +31,7 @@ pub fn router() -> axum::Router<EvaltorState> {
         .route("/assignments/{id}", get(assignment))
         .route("/assignments/{id}/attempts", get(get_attempts))
         .route("/assignments/{id}/attempts", post(post_attempt))
+        .route("/assignments/{id}/events", get(assignment_events))
 }
 
 async fn assignment(
@@ -31,6 +39,8 @@ async fn assignment(
     State(state): State<EvaltorState>,
     Path(assignment_id): Path<Uuid>,
 ) -> Result<Html<String>, StatusCode> {
+    authz::require(authz::can_view_assignment(&state.db_pool, &auth, assignment_id).await)?;
+
     let assignment = sqlx::query_as!(
         Assignment,
         r#"SELECT id as "id: uuid::Uuid", name, description FROM assignments WHERE id = ?"#,
@@ -40,10 +50,15 @@ async fn assignment(
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    let languages = Language::enabled_for_assignment(&state.db_pool, assignment_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
     AssignmentPage {
         user_name: auth.name.clone(),
         user_email: auth.email.clone(),
         assignment,
+        languages,
     }
     .render()
     .map(Html)
@@ -51,27 +66,144 @@ async fn assignment(
 }
 
 async fn get_attempts(
-    _auth: auth::AuthUser,
+    auth: auth::AuthUser,
     State(state): State<EvaltorState>,
     Path(assignment_id): Path<Uuid>,
 ) -> Result<Html<String>, StatusCode> {
-    let attempts = sqlx::query_as!(
-        Attempt,
+    authz::require(authz::can_view_assignment(&state.db_pool, &auth, assignment_id).await)?;
+
+    render_attempts(&state, &auth, assignment_id, None).await
+}
+
+/// Streams the progress of the attempts at the assignment the user may see.
+///
+/// A started compilation or runner is sent as the text its row shows, named
+/// `compilation-{attempt id}` or `runner-{id}`, for the page to swap in. Anything queued or
+/// finished is named `attempt-{id}-queued` or `attempt-{id}-finished`, so the page fetches
+/// just that attempt again.
+async fn assignment_events(
+    auth: auth::AuthUser,
+    State(state): State<EvaltorState>,
+    Path(assignment_id): Path<Uuid>,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, StatusCode> {
+    authz::require(authz::can_view_assignment(&state.db_pool, &auth, assignment_id).await)?;
+
+    let authors = authz::visible_authors(&state.db_pool, &auth, assignment_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let events = BroadcastStream::new(state.runner_manager.subscribe()).filter_map(move |event| {
+        // a subscriber that lagged behind only misses the events it dropped
+        let event = event.ok().filter(|e| {
+            e.assignment_id == assignment_id
+                && authors
+                    .as_ref()
+                    .is_none_or(|authors| authors.contains(&e.user_id))
+        })?;
+
+        Some(progress_event(&event))
+    });
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+fn progress_event(event: &RunnerEvent) -> Result<Event, axum::Error> {
+    match (event.stage, event.runner_id) {
+        (RunStage::Started, Some(runner_id)) => Ok(Event::default()
+            .event(format!("runner-{runner_id}"))
+            .data("Running...")),
+        (RunStage::Started, None) => Ok(Event::default()
+            .event(format!("compilation-{}", event.attempt_id))
+            .data("Compiling...")),
+        (RunStage::Queued, _) => Event::default()
+            .event(format!("attempt-{}-queued", event.attempt_id))
+            .json_data(event),
+        (RunStage::Finished { .. }, _) => Event::default()
+            .event(format!("attempt-{}-finished", event.attempt_id))
+            .json_data(event),
+    }
+}
+
+/// Renders the attempts of the assignment the user may see, with the reason their last
+/// submission was rejected.
+async fn render_attempts(
+    state: &EvaltorState,
+    user: &User,
+    assignment_id: Uuid,
+    rejection: Option<String>,
+) -> Result<Html<String>, StatusCode> {
+    let authors = authz::visible_authors(&state.db_pool, user, assignment_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let attempts = sqlx::query!(
         r#"SELECT
-                id as "id: uuid::Uuid",
-                assignment_id as "assignment_id: uuid::Uuid",
-                user_id as "user_id: uuid::Uuid",
-                submitted_at as "submitted_at: chrono::NaiveDateTime"
-                FROM attempts WHERE assignment_id = ? ORDER BY submitted_at DESC"#,
+                a.id as "id: uuid::Uuid",
+                a.assignment_id as "assignment_id: uuid::Uuid",
+                a.user_id as "user_id: uuid::Uuid",
+                a.language_id as "language_id: uuid::Uuid",
+                a.submitted_at as "submitted_at: chrono::NaiveDateTime",
+                u.name as "author!"
+                FROM attempts a
+                JOIN users u ON a.user_id = u.id
+                WHERE a.assignment_id = ? ORDER BY a.submitted_at"#,
         assignment_id
     )
     .fetch_all(&state.db_pool)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    let positions = state
+        .runner_manager
+        .queue_positions()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let mut counts = HashMap::<Uuid, usize>::new();
+
+    let mut attempts: Vec<_> = attempts
+        .into_iter()
+        .filter(|record| {
+            authors
+                .as_ref()
+                .is_none_or(|authors| authors.contains(&record.user_id))
+        })
+        .map(|record| {
+            let number = counts.entry(record.user_id).or_default();
+            *number += 1;
+
+            ListedAttempt {
+                number: *number,
+                author: (record.user_id != user.id).then_some(record.author),
+                queue_position: positions.get(&record.id).copied(),
+                attempt: Attempt {
+                    id: record.id,
+                    assignment_id: record.assignment_id,
+                    user_id: record.user_id,
+                    language_id: record.language_id,
+                    submitted_at: record.submitted_at,
+                },
+            }
+        })
+        .collect();
+
+    // newest first
+    attempts.reverse();
+
+    let quota = SubmissionQuota::for_user(
+        &state.db_pool,
+        assignment_id,
+        user.id,
+        chrono::Utc::now().naive_utc(),
+    )
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
     AttemptsPartial {
         assignment_id,
         attempts,
+        quota,
+        rejection,
     }
     .render()
     .map(Html)
@@ -81,6 +213,7 @@ async fn get_attempts(
 #[derive(Debug, TryFromMultipart)]
 pub struct PostAssignmentForm {
     pub assignment_id: Uuid,
+    pub language_id: Uuid,
     #[form_data(limit = "10MiB")]
     pub program: FieldData<Bytes>,
 }
@@ -90,82 +223,93 @@ async fn post_attempt(
     state: State<EvaltorState>,
     TypedMultipart(PostAssignmentForm {
         assignment_id,
+        language_id,
         program,
     }): TypedMultipart<PostAssignmentForm>,
 ) -> impl IntoResponse {
-    let attempt_id = Uuid::new_v4();
+    authz::require(authz::can_submit(&state.db_pool, &auth, assignment_id).await)?;
+
+    let language = Language::enabled_by_id(&state.db_pool, assignment_id, language_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::BAD_REQUEST)?;
 
     let attempt = Attempt {
-        id: attempt_id,
+        id: Uuid::new_v4(),
         assignment_id,
         user_id: auth.id,
+        language_id: language.id,
         submitted_at: chrono::Utc::now().naive_utc(),
     };
 
-    sqlx::query!(
-        "INSERT INTO attempts (id, assignment_id, user_id, submitted_at) VALUES (?, ?, ?, ?)",
-        attempt.id,
-        attempt.assignment_id,
-        auth.id,
-        attempt.submitted_at,
-    )
-    .execute(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    // htmx only swaps successful responses, so the rejection is rendered like a normal page
+    if state.runner_manager.is_shutting_down() {
+        let rejection = "The server is restarting, submit again in a minute.".to_owned();
 
-    let tests = sqlx::query_as!(
-        Test,
-        r#"SELECT
-            id as "id: uuid::Uuid",
-            name,
-            description,
-            type as "type_: TestType",
-            assignment_id as "assignment_id: uuid::Uuid",
-            points
-        FROM tests WHERE assignment_id = ?"#,
-        assignment_id
-    )
-    .fetch_all(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    let mountpoint = state
-        .config
-        .submissions
-        .join(assignment_id.to_string())
-        .join(auth.id.to_string())
-        .join(attempt_id.to_string());
+        return render_attempts(&state, &auth, assignment_id, Some(rejection))
+            .await
+            .map(IntoResponse::into_response);
+    }
 
-    dbg!(&mountpoint);
+    // the source is in place before the attempt counts, an attempt without one fails to judge
+    let dir = AttemptDir::new(&state.config.submissions, &attempt);
 
-    fs::create_dir_all(&mountpoint)
+    if write_workspace(&dir, &language, &program.contents)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .is_err()
+    {
+        remove_workspace(&dir).await;
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    let inserted = attempt.insert_within_quota(&state.db_pool).await;
+
+    if !matches!(inserted, Ok(Ok(()))) {
+        remove_workspace(&dir).await;
+    }
 
-    fs::write(mountpoint.join("main.py"), program.contents)
+    if let Err(rejection) = inserted.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? {
+        return render_attempts(&state, &auth, assignment_id, Some(rejection))
+            .await
+            .map(IntoResponse::into_response);
+    }
+
+    let plan = AttemptPlan::new(&state.db_pool, &state.config, &attempt, &language)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    for test in tests {
-        let blueprint = NSJailBlueprint {
-            tests: state.config.tests.clone(),
-            memory_limit: 512,
-            time_limit: 5,
-            max_cpus: 1,
-            disable_network: true,
-            mountpoint: mountpoint.clone(),
-            command: "/usr/bin/python3 main.py".to_owned(),
-            write_stdin: true,
-            quiet: true,
-        };
-
+    if state.config.cancel_superseded {
         state
             .runner_manager
-            .run_from_blueprint(blueprint, test.id, attempt.id);
+            .cancel_superseded(&attempt)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     }
 
-    Ok::<_, StatusCode>((
-        StatusCode::SEE_OTHER,
-        [("Location", format!("/assignments/{assignment_id}/attempts"))],
-    ))
+    state
+        .runner_manager
+        .evaluate_attempt(&attempt, plan)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok::<_, StatusCode>(
+        (
+            StatusCode::SEE_OTHER,
+            [("Location", format!("/assignments/{assignment_id}/attempts"))],
+        )
+            .into_response(),
+    )
+}
+
+/// Stores the submitted program where the sandbox mounts it, with an empty build directory.
+async fn write_workspace(dir: &AttemptDir, language: &Language, program: &[u8]) -> io::Result<()> {
+    fs::create_dir_all(&dir.workspace).await?;
+    fs::write(dir.workspace.join(&language.source_file), program).await?;
+    fs::create_dir_all(dir.build()).await
+}
+
+async fn remove_workspace(dir: &AttemptDir) {
+    if let Err(err) = fs::remove_dir_all(&dir.workspace).await {
+        eprintln!("Failed to remove {}: {err}", dir.workspace.display());
+    }
 }
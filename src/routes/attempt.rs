@@ -1,77 +1,158 @@
 use askama::Template;
 use axum::{
     Router,
-    extract::{Path, State},
-    response::{Html, IntoResponse},
-    routing::get,
+    extract::{Path, Query, State},
+    response::{
+        Html, IntoResponse,
+        sse::{Event, KeepAlive, Sse},
+    },
+    routing::{get, post},
 };
 use reqwest::StatusCode;
+use serde::Deserialize;
 use tokio::fs;
+use tokio_stream::{Stream, StreamExt, wrappers::BroadcastStream};
 use uuid::Uuid;
 
 use crate::{
-    auth,
+    auth, authz,
+    evaluation::AttemptDir,
+    models::{Attempt, Compilation, Language, NetworkPolicy, Runner, Verdict},
     state::EvaltorState,
-    templates::{RunnerResult, RunnersPartial},
+    templates::{CompilationResult, RunnerResult, RunnersPartial},
 };
 
 pub fn router() -> axum::Router<EvaltorState> {
     Router::new()
         .route("/attempts/{id}/runners", get(get_runners))
         .route("/attempts/{id}/source", get(get_attempt_source))
+        .route("/attempts/{id}/cancel", post(cancel_attempt))
+        .route("/attempts/{id}/rejudge", post(rejudge_attempt))
+        .route("/attempts/{id}/events", get(attempt_events))
+}
+
+/// Streams the progress of the attempt's compilation and runners as `runner` events.
+async fn attempt_events(
+    auth: auth::AuthUser,
+    State(state): State<EvaltorState>,
+    Path(attempt_id): Path<Uuid>,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, StatusCode> {
+    authz::require(authz::can_view_attempt(&state.db_pool, &auth, attempt_id).await)?;
+
+    let events = BroadcastStream::new(state.runner_manager.subscribe()).filter_map(move |event| {
+        let event = event.ok().filter(|e| e.attempt_id == attempt_id)?;
+
+        Some(Event::default().event("runner").json_data(event))
+    });
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+#[derive(Deserialize)]
+struct RunnersQuery {
+    verdict: Option<String>,
 }
 
 async fn get_runners(
-    _auth: auth::AuthUser,
+    auth: auth::AuthUser,
     State(state): State<EvaltorState>,
     Path(attempt_id): Path<Uuid>,
+    Query(query): Query<RunnersQuery>,
 ) -> Result<Html<String>, StatusCode> {
-    let mut total_test_points = 0;
-    let mut total_runner_points = 0;
+    authz::require(authz::can_view_attempt(&state.db_pool, &auth, attempt_id).await)?;
+
+    let verdict_filter = query.verdict.as_deref().and_then(Verdict::parse);
+
+    let compilation = Compilation::latest_for_attempt(&state.db_pool, attempt_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map(CompilationResult::from);
 
-    let runners = sqlx::query!(
+    let mut runners = sqlx::query!(
         r#"SELECT
+            r.id as "id: Uuid",
             t.name as "test_name!",
             t.description as "test_description!",
             t.points as "test_points!",
             r.passed as "passed: bool",
+            r.verdict as "verdict: Verdict",
+            r.started_at as "started_at: chrono::NaiveDateTime",
             r.finished_at as "finished_at: chrono::NaiveDateTime",
             r.stdout as "stdout: Vec<u8>",
             r.expected_stdout as "expected_stdout: Vec<u8>",
-            r.points as "runner_points!"
+            r.points as "runner_points!",
+            r.memory_limit,
+            r.time_limit,
+            r.max_cpus,
+            r.network_policy as "network_policy: NetworkPolicy",
+            r.wall_time_ms,
+            r.cpu_time_ms,
+            r.peak_memory_kb
         FROM runners r
         JOIN tests t ON r.test_id = t.id
         WHERE r.attempt_id = ?
+        AND NOT EXISTS (
+            SELECT 1 FROM runners r2
+            WHERE r2.attempt_id = r.attempt_id
+            AND r2.test_id = r.test_id
+            AND r2.created_at > r.created_at
+        )
         ORDER BY t.name"#,
-        attempt_id
+        attempt_id,
     )
     .fetch_all(&state.db_pool)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
     .into_iter()
-    .map(|record| {
-        total_test_points += record.test_points;
-        total_runner_points += record.runner_points;
-
-        RunnerResult {
-            finished_at: record.finished_at,
-            passed: record.passed,
-            stdout: record.stdout.and_then(|out| String::from_utf8(out).ok()),
-            expected_stdout: record
-                .expected_stdout
-                .and_then(|out| String::from_utf8(out).ok()),
-            test_name: record.test_name,
-            test_description: record.test_description,
-
-            test_points: record.test_points,
-            runner_points: record.runner_points,
-        }
+    .map(|record| RunnerResult {
+        id: record.id,
+        started_at: record.started_at,
+        finished_at: record.finished_at,
+        passed: record.passed,
+        verdict: record.verdict,
+        stdout: record.stdout.and_then(|out| String::from_utf8(out).ok()),
+        expected_stdout: record
+            .expected_stdout
+            .and_then(|out| String::from_utf8(out).ok()),
+        test_name: record.test_name,
+        test_description: record.test_description,
+
+        test_points: record.test_points,
+        runner_points: record.runner_points,
+
+        memory_limit: record.memory_limit,
+        time_limit: record.time_limit,
+        max_cpus: record.max_cpus,
+        network_policy: record.network_policy,
+
+        wall_time_ms: record.wall_time_ms,
+        cpu_time_ms: record.cpu_time_ms,
+        peak_memory_kb: record.peak_memory_kb,
     })
-    .collect();
+    .collect::<Vec<_>>();
+
+    // the score is the attempt's, whichever verdicts are shown and whatever is being rejudged
+    let total_test_points = runners.iter().map(|runner| runner.test_points).sum();
+    let total_runner_points = Runner::scored_points(&state.db_pool, attempt_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    runners.retain(|runner| verdict_filter.is_none_or(|verdict| runner.verdict == Some(verdict)));
+
+    let queue_position = state
+        .runner_manager
+        .queue_positions()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .get(&attempt_id)
+        .copied();
 
     RunnersPartial {
         attempt_id,
+        compilation,
         runners,
+        verdict_filter,
+        queue_position,
 
         total_test_points,
         total_runner_points,
@@ -86,36 +167,19 @@ pub async fn get_attempt_source(
     State(state): State<EvaltorState>,
     Path(attempt_id): Path<Uuid>,
 ) -> Result<impl IntoResponse, StatusCode> {
-    let submissions_dir = state.config.submissions;
-    let user_id = auth.id;
+    authz::require(authz::can_view_attempt(&state.db_pool, &auth, attempt_id).await)?;
 
-    let attempt_user_id = sqlx::query!(
-        r#"SELECT user_id as "user_id: Uuid" FROM attempts WHERE id = ?"#,
-        attempt_id
-    )
-    .fetch_one(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::NOT_FOUND)?;
-
-    if attempt_user_id.user_id != user_id {
-        return Err(StatusCode::FORBIDDEN);
-    }
-
-    let assignment = sqlx::query!(
-        r#"SELECT assignment_id as "id: Uuid" FROM attempts WHERE id = ?"#,
-        attempt_id
-    )
-    .fetch_one(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::NOT_FOUND)?;
+    let attempt = Attempt::by_id(&state.db_pool, attempt_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
 
-    let assignment_id = assignment.id;
+    let language = Language::for_attempt(&state.db_pool, attempt_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
 
-    let source_path = submissions_dir
-        .join(assignment_id.to_string())
-        .join(user_id.to_string())
-        .join(attempt_id.to_string())
-        .join("main.py");
+    let source_path = AttemptDir::new(&state.config.submissions, &attempt)
+        .workspace
+        .join(language.source_file);
 
     let source = fs::read_to_string(source_path)
         .await
@@ -123,3 +187,41 @@ pub async fn get_attempt_source(
 
     Ok(source)
 }
+
+/// Lets the staff of the attempt's class stop its evaluation, e.g. when it is stuck.
+async fn cancel_attempt(
+    auth: auth::AuthUser,
+    State(state): State<EvaltorState>,
+    Path(attempt_id): Path<Uuid>,
+) -> Result<impl IntoResponse, StatusCode> {
+    authz::require(authz::can_manage_attempt(&state.db_pool, &auth, attempt_id).await)?;
+
+    state
+        .runner_manager
+        .cancel_attempt(attempt_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok([("HX-Refresh", "true")])
+}
+
+/// Lets the staff of the attempt's class evaluate it again, e.g. after a system error.
+async fn rejudge_attempt(
+    auth: auth::AuthUser,
+    State(state): State<EvaltorState>,
+    Path(attempt_id): Path<Uuid>,
+) -> Result<impl IntoResponse, StatusCode> {
+    authz::require(authz::can_manage_attempt(&state.db_pool, &auth, attempt_id).await)?;
+
+    let attempt = Attempt::by_id(&state.db_pool, attempt_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    state
+        .runner_manager
+        .rejudge_attempt(&attempt)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok([("HX-Refresh", "true")])
+}
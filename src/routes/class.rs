@@ -1,26 +1,49 @@
 use askama::Template;
 use axum::{
     Form, Router,
+    body::Bytes,
     extract::{Path, State},
     response::{Html, IntoResponse, Redirect},
     routing::{get, post},
 };
+use axum_typed_multipart::{FieldData, TryFromMultipart, TypedMultipart};
 use reqwest::StatusCode;
 use serde::Deserialize;
 use uuid::Uuid;
 
 use crate::{
-    auth,
-    models::{Assignment, Class, User, UserAssignment},
+    auth, authz,
+    models::{
+        Assignment, Attempt, Class, ClassMember, ClassRole, JobStatus, Language, PendingEnrollment,
+        RosterRow, Test, User, UserAssignment,
+    },
     state::EvaltorState,
-    templates::{AssignmentPage, ClassPage},
+    templates::{
+        ActiveJob, AssignmentPage, ClassPage, JobsPage, RosterMember, RosterPage, SystemErrorRun,
+        SystemErrorsPage,
+    },
 };
 
 pub fn router() -> axum::Router<EvaltorState> {
     Router::new()
-        .route("/classes/{id}/assign", post(assign_to_student))
+        .route("/classes/join", post(join_class))
+        .route("/classes/{class_id}/assign", post(assign_to_student))
+        .route("/classes/{class_id}/roster", get(get_roster))
+        .route("/classes/{class_id}/roster/import", post(import_roster))
+        .route("/classes/{class_id}/join-code", post(regenerate_join_code))
+        .route(
+            "/classes/{class_id}/members/{user_id}/remove",
+            post(remove_member),
+        )
+        .route(
+            "/classes/{class_id}/members/{user_id}/move",
+            post(move_member),
+        )
+        .route("/classes/{class_id}/system-errors", get(get_system_errors))
+        .route("/classes/{class_id}/jobs", get(get_jobs))
+        .route("/classes/{class_id}/rejudge", post(rejudge))
         .route("/classes/{class_id}/{assignment_id}", get(class_assignment))
-        .route("/classes/{id}", get(get_class))
+        .route("/classes/{class_id}", get(get_class))
 }
 
 #[derive(Deserialize)]
@@ -30,14 +53,18 @@ struct AssignToStudentForm {
 }
 
 async fn assign_to_student(
-    _auth: auth::AuthUser,
+    auth::TeacherOf { class_id, .. }: auth::TeacherOf,
     State(state): State<EvaltorState>,
-    Path(class_id): Path<Uuid>,
     Form(AssignToStudentForm {
         user_id,
         assignment_id,
     }): Form<AssignToStudentForm>,
 ) -> Result<Redirect, StatusCode> {
+    ClassMember::role_of(&state.db_pool, class_id, user_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::BAD_REQUEST)?;
+
     UserAssignment::assign_to_student(&state.db_pool, user_id, assignment_id, class_id)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
@@ -45,11 +72,224 @@ async fn assign_to_student(
     Ok(Redirect::to(&format!("/classes/{class_id}")))
 }
 
+#[derive(Deserialize)]
+struct JoinClassForm {
+    code: String,
+}
+
+/// Makes the user a student of the class with the code, members keep the role they have.
+async fn join_class(
+    auth: auth::AuthUser,
+    State(state): State<EvaltorState>,
+    Form(JoinClassForm { code }): Form<JoinClassForm>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let Some(class_id) = Class::by_join_code(&state.db_pool, &code)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    else {
+        // htmx only swaps successful responses, so the rejection is a normal one
+        return Ok("No class has this code.".into_response());
+    };
+
+    ClassMember::add(&state.db_pool, class_id, auth.id, ClassRole::Student)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok([("HX-Redirect", format!("/classes/{class_id}"))].into_response())
+}
+
+async fn get_roster(
+    auth::TeacherOf { user, class_id }: auth::TeacherOf,
+    State(state): State<EvaltorState>,
+) -> Result<Html<String>, StatusCode> {
+    render_roster(&state, user, class_id, None).await
+}
+
+#[derive(Debug, TryFromMultipart)]
+struct ImportRosterForm {
+    #[form_data(limit = "1MiB")]
+    roster: FieldData<Bytes>,
+    /// Assignments every imported student gets
+    #[form_data(field_name = "assignment_id")]
+    assignment_ids: Vec<Uuid>,
+}
+
+/// Enrolls the students of a CSV roster, those who have not signed in with the email yet once
+/// they do.
+async fn import_roster(
+    auth::TeacherOf { user, class_id }: auth::TeacherOf,
+    State(state): State<EvaltorState>,
+    TypedMultipart(ImportRosterForm {
+        roster,
+        assignment_ids,
+    }): TypedMultipart<ImportRosterForm>,
+) -> Result<Html<String>, StatusCode> {
+    let message = match RosterRow::parse(&roster.contents) {
+        Ok(rows) => {
+            let import =
+                PendingEnrollment::import(&state.db_pool, class_id, &rows, &assignment_ids)
+                    .await
+                    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+            format!(
+                "Enrolled {} students, {} more once they sign in with their email.",
+                import.enrolled, import.pending
+            )
+        }
+        Err(err) => format!("The roster was not imported: {err}"),
+    };
+
+    render_roster(&state, user, class_id, Some(message)).await
+}
+
+/// Renders the roster, with the outcome of the import that was just made.
+async fn render_roster(
+    state: &EvaltorState,
+    user: User,
+    class_id: Uuid,
+    import_message: Option<String>,
+) -> Result<Html<String>, StatusCode> {
+    let class = sqlx::query_as!(
+        Class,
+        r#"SELECT id as "id: uuid::Uuid", creator_id as "creator_id: uuid::Uuid", name, description FROM classes WHERE id = ?"#,
+        class_id
+    )
+    .fetch_one(&state.db_pool)
+    .await
+    .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    let join_code = Class::join_code(&state.db_pool, class_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let members = sqlx::query_as!(
+        RosterMember,
+        r#"SELECT
+            u.id as "user_id: uuid::Uuid",
+            u.name,
+            u.email,
+            u.student_id,
+            cm.role as "role: ClassRole"
+        FROM class_members cm
+        JOIN users u ON cm.user_id = u.id
+        WHERE cm.class_id = ?
+        ORDER BY cm.role DESC, u.name"#,
+        class_id
+    )
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let pending = PendingEnrollment::for_class(&state.db_pool, class_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let all_assignments = Assignment::all(&state.db_pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let other_classes = Class::taught_by(&state.db_pool, &user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .into_iter()
+        .filter(|other| other.id != class_id)
+        .collect();
+
+    RosterPage {
+        user_name: user.name,
+        user_email: user.email,
+        class,
+        join_code,
+        members,
+        pending,
+        all_assignments,
+        other_classes,
+        import_message,
+    }
+    .render()
+    .map(Html)
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+async fn regenerate_join_code(
+    auth::TeacherOf { class_id, .. }: auth::TeacherOf,
+    State(state): State<EvaltorState>,
+) -> Result<Redirect, StatusCode> {
+    Class::regenerate_join_code(&state.db_pool, class_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Redirect::to(&format!("/classes/{class_id}/roster")))
+}
+
+/// Checks the member is a student, the roster does not manage the staff.
+async fn ensure_student(
+    state: &EvaltorState,
+    class_id: Uuid,
+    user_id: Uuid,
+) -> Result<(), StatusCode> {
+    let role = ClassMember::role_of(&state.db_pool, class_id, user_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if role == Some(ClassRole::Student) {
+        Ok(())
+    } else {
+        Err(StatusCode::BAD_REQUEST)
+    }
+}
+
+async fn remove_member(
+    auth::TeacherOf { class_id, .. }: auth::TeacherOf,
+    State(state): State<EvaltorState>,
+    Path((_, user_id)): Path<(Uuid, Uuid)>,
+) -> Result<Redirect, StatusCode> {
+    ensure_student(&state, class_id, user_id).await?;
+
+    ClassMember::remove(&state.db_pool, class_id, user_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Redirect::to(&format!("/classes/{class_id}/roster")))
+}
+
+#[derive(Deserialize)]
+struct MoveMemberForm {
+    class_id: Uuid,
+}
+
+/// Moves the student to another class the teacher teaches.
+async fn move_member(
+    auth::TeacherOf { user, class_id }: auth::TeacherOf,
+    State(state): State<EvaltorState>,
+    Path((_, user_id)): Path<(Uuid, Uuid)>,
+    Form(MoveMemberForm { class_id: to }): Form<MoveMemberForm>,
+) -> Result<Redirect, StatusCode> {
+    ensure_student(&state, class_id, user_id).await?;
+
+    let role = auth::class_role(&state.db_pool, to, &user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if to == class_id || role != Some(ClassRole::Teacher) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    ClassMember::move_student(&state.db_pool, class_id, to, user_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Redirect::to(&format!("/classes/{class_id}/roster")))
+}
+
 async fn class_assignment(
     auth: auth::AuthUser,
     State(state): State<EvaltorState>,
     Path((class_id, assignment_id)): Path<(Uuid, Uuid)>,
 ) -> impl IntoResponse {
+    authz::require(authz::can_view_class(&state.db_pool, &auth, class_id).await)?;
+    authz::require(authz::can_view_assignment(&state.db_pool, &auth, assignment_id).await)?;
+
     let assignment_id = sqlx::query!(
         r#"SELECT assignment_id as "id: uuid::Uuid" FROM user_assignments WHERE class_id = ? and assignment_id = ?"#,
         class_id,
@@ -68,12 +308,15 @@ async fn class_assignment(
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    dbg!(&assignment);
+    let languages = Language::enabled_for_assignment(&state.db_pool, assignment.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     AssignmentPage {
         user_name: auth.0.name,
         user_email: auth.0.email,
         assignment,
+        languages,
     }
     .render()
     .map(Html)
@@ -85,6 +328,8 @@ async fn get_class(
     State(state): State<EvaltorState>,
     Path(class_id): Path<Uuid>,
 ) -> Result<Html<String>, StatusCode> {
+    authz::require(authz::can_view_class(&state.db_pool, &auth, class_id).await)?;
+
     let class = sqlx::query_as!(
         Class,
         r#"SELECT id as "id: uuid::Uuid", creator_id as "creator_id: uuid::Uuid", name, description FROM classes WHERE id = ?"#,
@@ -103,25 +348,200 @@ async fn get_class(
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    let all_users = User::all(&state.db_pool)
+    let role = auth::class_role(&state.db_pool, class_id, &auth)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    let all_assignments = Assignment::all(&state.db_pool)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut students = Vec::new();
+    let mut all_assignments = Vec::new();
+    let mut rejudge_targets = Vec::new();
+
+    if role == Some(ClassRole::Teacher) {
+        students = ClassMember::students(&state.db_pool, class_id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        all_assignments = Assignment::all(&state.db_pool)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        for assignment in Assignment::for_class(&state.db_pool, class_id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        {
+            let tests = Test::for_assignment(&state.db_pool, assignment.id)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+            rejudge_targets.push((assignment, tests));
+        }
+    }
 
     ClassPage {
         user_name: auth.0.name,
         user_email: auth.0.email,
-        user_id: auth.0.id,
+        role,
         class,
         assignments,
         points,
-        all_users,
+        students,
         all_assignments,
+        rejudge_targets,
+    }
+    .render()
+    .map(Html)
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+async fn get_system_errors(
+    auth::StaffOf { user, class_id }: auth::StaffOf,
+    State(state): State<EvaltorState>,
+) -> Result<Html<String>, StatusCode> {
+    let class = sqlx::query_as!(
+        Class,
+        r#"SELECT id as "id: uuid::Uuid", creator_id as "creator_id: uuid::Uuid", name, description FROM classes WHERE id = ?"#,
+        class_id
+    )
+    .fetch_one(&state.db_pool)
+    .await
+    .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    let runs = sqlx::query_as!(
+        SystemErrorRun,
+        r#"SELECT
+            r.id as "runner_id: uuid::Uuid",
+            r.attempt_id as "attempt_id: uuid::Uuid",
+            s.name as "assignment_name!",
+            t.name as "test_name!",
+            u.name as "user_name!",
+            u.email as "user_email!",
+            r.finished_at as "finished_at: chrono::NaiveDateTime",
+            r.error_reason
+        FROM runners r
+        JOIN attempts a ON r.attempt_id = a.id
+        JOIN user_assignments ua ON ua.assignment_id = a.assignment_id AND ua.user_id = a.user_id
+        JOIN assignments s ON a.assignment_id = s.id
+        JOIN tests t ON r.test_id = t.id
+        JOIN users u ON a.user_id = u.id
+        WHERE ua.class_id = ? AND r.verdict = 'system_error'
+        AND NOT EXISTS (
+            SELECT 1 FROM runners r2
+            WHERE r2.attempt_id = r.attempt_id
+            AND r2.test_id = r.test_id
+            AND r2.created_at > r.created_at
+        )
+        ORDER BY r.finished_at DESC"#,
+        class_id
+    )
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    SystemErrorsPage {
+        user_name: user.name,
+        user_email: user.email,
+        class,
+        runs,
+    }
+    .render()
+    .map(Html)
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+async fn get_jobs(
+    auth::StaffOf { user, class_id }: auth::StaffOf,
+    State(state): State<EvaltorState>,
+) -> Result<Html<String>, StatusCode> {
+    let class = sqlx::query_as!(
+        Class,
+        r#"SELECT id as "id: uuid::Uuid", creator_id as "creator_id: uuid::Uuid", name, description FROM classes WHERE id = ?"#,
+        class_id
+    )
+    .fetch_one(&state.db_pool)
+    .await
+    .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    let jobs = sqlx::query_as!(
+        ActiveJob,
+        r#"SELECT
+            j.attempt_id as "attempt_id: uuid::Uuid",
+            j.status as "status: JobStatus",
+            j.tries,
+            s.name as "assignment_name!",
+            t.name as "test_name?",
+            u.name as "user_name!",
+            u.email as "user_email!",
+            j.queued_at as "queued_at: chrono::NaiveDateTime",
+            j.claimed_at as "claimed_at: chrono::NaiveDateTime",
+            CASE WHEN j.status = 'running' THEN j.worker END as "worker?: String"
+        FROM jobs j
+        JOIN attempts a ON j.attempt_id = a.id
+        JOIN user_assignments ua ON ua.assignment_id = a.assignment_id AND ua.user_id = a.user_id
+        JOIN assignments s ON a.assignment_id = s.id
+        JOIN users u ON a.user_id = u.id
+        LEFT JOIN runners r ON j.runner_id = r.id
+        LEFT JOIN tests t ON r.test_id = t.id
+        WHERE ua.class_id = ? AND j.status IN ('queued', 'running')
+        ORDER BY j.queued_at"#,
+        class_id
+    )
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    JobsPage {
+        user_name: user.name,
+        user_email: user.email,
+        class,
+        jobs,
     }
     .render()
     .map(Html)
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
 }
+
+/// Exactly one of the fields is sent, depending on which form was used.
+#[derive(Deserialize)]
+struct RejudgeForm {
+    assignment_id: Option<Uuid>,
+    test_id: Option<Uuid>,
+}
+
+/// Rejudges every attempt at an assignment, or a single test of it, by students of the class.
+async fn rejudge(
+    auth::TeacherOf { class_id, .. }: auth::TeacherOf,
+    State(state): State<EvaltorState>,
+    Form(RejudgeForm {
+        assignment_id,
+        test_id,
+    }): Form<RejudgeForm>,
+) -> Result<Redirect, StatusCode> {
+    let test = match test_id {
+        Some(test_id) => Some(
+            Test::by_id(&state.db_pool, test_id)
+                .await
+                .map_err(|_| StatusCode::NOT_FOUND)?,
+        ),
+        None => None,
+    };
+
+    let assignment_id = test
+        .as_ref()
+        .map(|test| test.assignment_id)
+        .or(assignment_id)
+        .ok_or(StatusCode::BAD_REQUEST)?;
+
+    let attempts = Attempt::for_class_assignment(&state.db_pool, class_id, assignment_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    for attempt in attempts {
+        match &test {
+            Some(test) => state.runner_manager.rejudge_test(&attempt, test.id).await,
+            None => state.runner_manager.rejudge_attempt(&attempt).await,
+        }
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    }
+
+    Ok(Redirect::to(&format!("/classes/{class_id}/jobs")))
+}
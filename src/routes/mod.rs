@@ -1,3 +1,4 @@
 pub mod assignment;
 pub mod attempt;
 pub mod class;
+pub mod worker;
@@ -0,0 +1,135 @@
+use std::time::Duration;
+
+use axum::{
+    Json, Router,
+    extract::{DefaultBodyLimit, FromRequestParts, Path, State},
+    http::{header, request::Parts},
+    response::IntoResponse,
+    routing::{get, post},
+};
+use reqwest::StatusCode;
+use uuid::Uuid;
+
+use crate::{
+    state::EvaltorState,
+    worker::{ClaimRequest, Heartbeat, WorkResult},
+};
+
+/// How long a claim waits for a job before the worker has to ask again.
+const CLAIM_WAIT: Duration = Duration::from_secs(25);
+/// Results carry the whole build output of compile jobs.
+const MAX_RESULT_SIZE: usize = 256 * 1024 * 1024;
+
+/// Routes `evaltor-worker` talks to, see [`crate::worker`].
+pub fn router() -> axum::Router<EvaltorState> {
+    Router::new()
+        .route("/worker/claim", post(claim))
+        .route("/worker/jobs/{id}/files", get(get_files))
+        .route("/worker/jobs/{id}/heartbeat", post(heartbeat))
+        .route("/worker/jobs/{id}/result", post(post_result))
+        .layer(DefaultBodyLimit::max(MAX_RESULT_SIZE))
+}
+
+/// A request carrying the configured worker secret as its bearer token.
+struct WorkerAuth;
+
+impl FromRequestParts<EvaltorState> for WorkerAuth {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &EvaltorState,
+    ) -> Result<Self, Self::Rejection> {
+        let Some(secret) = &state.config.worker_secret else {
+            return Err(StatusCode::NOT_FOUND);
+        };
+
+        let token = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+
+        if constant_time_eq(token.as_bytes(), secret.as_bytes()) {
+            Ok(Self)
+        } else {
+            Err(StatusCode::UNAUTHORIZED)
+        }
+    }
+}
+
+/// Compares without returning early, so the secret cannot be guessed byte by byte.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+async fn claim(
+    _auth: WorkerAuth,
+    State(state): State<EvaltorState>,
+    Json(request): Json<ClaimRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let item = state
+        .runner_manager
+        .claim_remote(&request.worker, CLAIM_WAIT)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(match item {
+        Some(item) => Json(item).into_response(),
+        None => StatusCode::NO_CONTENT.into_response(),
+    })
+}
+
+async fn get_files(
+    _auth: WorkerAuth,
+    State(state): State<EvaltorState>,
+    Path(job_id): Path<Uuid>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let files = state
+        .runner_manager
+        .remote_files(job_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(files))
+}
+
+async fn heartbeat(
+    _auth: WorkerAuth,
+    State(state): State<EvaltorState>,
+    Path(job_id): Path<Uuid>,
+    Json(heartbeat): Json<Heartbeat>,
+) -> Result<StatusCode, StatusCode> {
+    let keep_running = state
+        .runner_manager
+        .heartbeat_remote(job_id, heartbeat.tries)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(if keep_running {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::CONFLICT
+    })
+}
+
+async fn post_result(
+    _auth: WorkerAuth,
+    State(state): State<EvaltorState>,
+    Path(job_id): Path<Uuid>,
+    Json(result): Json<WorkResult>,
+) -> Result<StatusCode, StatusCode> {
+    let accepted = state
+        .runner_manager
+        .complete_remote(job_id, result)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(if accepted {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::CONFLICT
+    })
+}
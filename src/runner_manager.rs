@@ -1,20 +1,1039 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use chrono::TimeDelta;
+use serde::Serialize;
 use sqlx::SqlitePool;
+use tokio::sync::{Notify, broadcast, watch};
 use uuid::Uuid;
 
-use crate::nsjail::{Instance, NSJailBlueprint};
+use crate::EvaltorArgs;
+use crate::evaluation::{self, AttemptDir, AttemptPlan};
+use crate::models::{
+    Attempt, Compilation, CompilationOutcome, Job, JobKind, JobLane, JobStatus, Language, Limits,
+    Runner, Verdict,
+};
+use crate::nsjail::{self, Instance};
+use crate::sandbox::{CancelSignal, Sandbox, SandboxOutput};
+use crate::worker::{self, WorkFile, WorkFiles, WorkItem, WorkResult};
+
+/// How long a claimed job belongs to its worker without a heartbeat.
+const LEASE: TimeDelta = TimeDelta::seconds(60);
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(20);
+/// How many times a job is run before its compilation or runner becomes a system error.
+const MAX_TRIES: i64 = 3;
+/// How often idle workers look for jobs whose lease ran out.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// How long killed jobs get to be put back into the queue during a shutdown.
+const KILL_TIMEOUT: Duration = Duration::from_secs(5);
+/// Recorded on compilations and runners a shutdown interrupted, until they run again.
+const SHUTDOWN_REASON: &str = "interrupted by a server shutdown";
+
+/// How many events a slow subscriber may fall behind before it misses some.
+const EVENT_BUFFER: usize = 256;
+
+/// Progress of a compilation or runner, published by [`RunnerManager`] as it happens.
+#[derive(Clone, Debug, Serialize)]
+pub struct RunnerEvent {
+    pub assignment_id: Uuid,
+    pub attempt_id: Uuid,
+    /// Author of the attempt, only for deciding who may see the event
+    #[serde(skip)]
+    pub user_id: Uuid,
+    /// `None` for the compilation of the attempt
+    pub runner_id: Option<Uuid>,
+    #[serde(flatten)]
+    pub stage: RunStage,
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(tag = "stage", rename_all = "snake_case")]
+pub enum RunStage {
+    Queued,
+    Started,
+    /// Compilations finish without a verdict
+    Finished {
+        verdict: Option<Verdict>,
+        points: i64,
+    },
+}
 
+/// A job running in this process, by job ID in [`RunnerManager`].
+#[derive(Debug)]
+struct RunningJob {
+    attempt_id: Uuid,
+    cancel: watch::Sender<bool>,
+}
+
+/// Runs evaluations on a fixed number of judge slots, fed from the persistent `jobs` queue.
 #[derive(Clone, Debug)]
 pub struct RunnerManager {
     db: SqlitePool,
+    sandbox: Arc<dyn Sandbox>,
+    config: EvaltorArgs,
+    available: Arc<Notify>,
+    running: Arc<Mutex<HashMap<Uuid, RunningJob>>>,
+    /// Notified whenever a job stops running in this process
+    idle: Arc<Notify>,
+    shutting_down: Arc<AtomicBool>,
+    events: broadcast::Sender<RunnerEvent>,
 }
 
 impl RunnerManager {
-    pub const fn new(db: SqlitePool) -> Self {
-        Self { db }
+    /// Requeues jobs left running by the previous run of the server and starts the judge
+    /// slots, slot N pinned to CPU N if configured.
+    pub async fn start(
+        db: SqlitePool,
+        sandbox: Arc<dyn Sandbox>,
+        config: EvaltorArgs,
+    ) -> sqlx::Result<Self> {
+        let requeued = Job::requeue_running(&db).await?;
+
+        if requeued > 0 {
+            eprintln!("Requeued {requeued} jobs interrupted by a restart");
+        }
+
+        let manager = Self {
+            db,
+            sandbox,
+            available: Arc::default(),
+            running: Arc::default(),
+            idle: Arc::default(),
+            shutting_down: Arc::default(),
+            events: broadcast::channel(EVENT_BUFFER).0,
+            config,
+        };
+
+        for slot in 0..manager.config.judge_workers {
+            tokio::spawn(
+                manager
+                    .clone()
+                    .slot(manager.config.pin_cpus.then_some(slot)),
+            );
+        }
+
+        Ok(manager)
+    }
+
+    /// Records the attempt's compilation and runners as queued and enqueues them.
+    ///
+    /// The tests only enter the queue once the build succeeded, ahead of everything that was
+    /// submitted in the meantime.
+    pub async fn evaluate_attempt(&self, attempt: &Attempt, plan: AttemptPlan) -> sqlx::Result<()> {
+        self.enqueue(attempt, plan, JobLane::Normal).await
+    }
+
+    /// Evaluates the attempt again from scratch with the current tests and limits.
+    ///
+    /// The earlier runs are kept as history, only the newest run of every test that finished
+    /// with a verdict about the program is scored.
+    pub async fn rejudge_attempt(&self, attempt: &Attempt) -> sqlx::Result<()> {
+        self.cancel_attempt(attempt.id).await?;
+
+        let language = Language::for_attempt(&self.db, attempt.id).await?;
+        let plan = AttemptPlan::new(&self.db, &self.config, attempt, &language).await?;
+
+        self.enqueue(attempt, plan, JobLane::Rejudge).await
+    }
+
+    /// Runs a single test of the attempt again, reusing its build.
+    ///
+    /// Attempts that failed to build are left alone, their tests would fail the same way. A
+    /// build that was cancelled or hit a system error is run again with the test behind it.
+    pub async fn rejudge_test(&self, attempt: &Attempt, test_id: Uuid) -> sqlx::Result<()> {
+        let build = Compilation::latest_for_attempt(&self.db, attempt.id)
+            .await?
+            .map(|c| (c.finished_at.is_some(), c.outcome));
+
+        if build == Some((true, Some(CompilationOutcome::Failed))) {
+            return Ok(());
+        }
+
+        let language = Language::for_attempt(&self.db, attempt.id).await?;
+        let limits = Limits::for_test(&self.db, test_id, language.id).await?;
+        let dir = AttemptDir::new(&self.config.submissions, attempt);
+
+        let runner = evaluation::test_blueprint(&self.config, &language, limits, &dir)
+            .pending_runner(test_id, attempt.id);
+        runner.insert_new(&self.db).await?;
+        self.publish(attempt, Some(runner.id), RunStage::Queued);
+
+        let rebuild = match build {
+            // a build still in progress queues the runner itself once it succeeds
+            Some((false, _)) => return Ok(()),
+            None | Some((true, Some(CompilationOutcome::Succeeded))) => None,
+            Some((true, _)) => evaluation::compile_blueprint(&self.config, &language, &dir),
+        };
+
+        if let Some(blueprint) = rebuild {
+            // the new build queues the runner once it succeeds, like the build of a submission
+            let compilation = blueprint.pending_compilation(attempt.id);
+            compilation.insert_new(&self.db).await?;
+            self.publish(attempt, None, RunStage::Queued);
+
+            Job::compile(&compilation, attempt.user_id, JobLane::Rejudge)
+                .insert_new(&self.db)
+                .await?;
+        } else {
+            let queued_at = chrono::Utc::now().naive_utc();
+
+            Job::test(&runner, attempt.user_id, JobLane::Rejudge, queued_at)
+                .insert_new(&self.db)
+                .await?;
+        }
+
+        self.available.notify_waiters();
+
+        Ok(())
+    }
+
+    async fn enqueue(
+        &self,
+        attempt: &Attempt,
+        plan: AttemptPlan,
+        lane: JobLane,
+    ) -> sqlx::Result<()> {
+        let mut runners = Vec::new();
+
+        for (test_id, blueprint) in plan.tests {
+            let runner = blueprint.pending_runner(test_id, attempt.id);
+            runner.insert_new(&self.db).await?;
+            self.publish(attempt, Some(runner.id), RunStage::Queued);
+            runners.push(runner);
+        }
+
+        if let Some(blueprint) = plan.compile {
+            let compilation = blueprint.pending_compilation(attempt.id);
+            compilation.insert_new(&self.db).await?;
+            self.publish(attempt, None, RunStage::Queued);
+
+            Job::compile(&compilation, attempt.user_id, lane)
+                .insert_new(&self.db)
+                .await?;
+        } else {
+            let queued_at = chrono::Utc::now().naive_utc();
+
+            for runner in &runners {
+                Job::test(runner, attempt.user_id, lane, queued_at)
+                    .insert_new(&self.db)
+                    .await?;
+            }
+        }
+
+        self.available.notify_waiters();
+
+        Ok(())
+    }
+
+    /// Stops everything of the attempt that has not finished yet, killing the running sandboxes.
+    pub async fn cancel_attempt(&self, attempt_id: Uuid) -> sqlx::Result<()> {
+        Job::cancel_for_attempt(&self.db, attempt_id).await?;
+        Compilation::cancel_unfinished(&self.db, attempt_id).await?;
+        let cancelled = Runner::cancel_unfinished(&self.db, attempt_id).await?;
+
+        {
+            let running = self.running.lock().unwrap_or_else(PoisonError::into_inner);
+
+            for job in running.values() {
+                if job.attempt_id == attempt_id {
+                    job.cancel.send_replace(true);
+                }
+            }
+        }
+
+        if !cancelled.is_empty() {
+            let attempt = Attempt::by_id(&self.db, attempt_id).await?;
+
+            for runner_id in cancelled {
+                let stage = RunStage::Finished {
+                    verdict: Some(Verdict::Cancelled),
+                    points: 0,
+                };
+
+                self.publish(&attempt, Some(runner_id), stage);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Cancels the earlier attempts of the same user at the same assignment, only the latest
+    /// attempt counts anyway.
+    pub async fn cancel_superseded(&self, attempt: &Attempt) -> sqlx::Result<()> {
+        let superseded = sqlx::query!(
+            r#"SELECT DISTINCT a.id as "id: Uuid"
+            FROM attempts a
+            JOIN jobs j ON j.attempt_id = a.id
+            WHERE a.assignment_id = ? AND a.user_id = ? AND a.id != ?
+            AND j.status IN ('queued', 'running')"#,
+            attempt.assignment_id,
+            attempt.user_id,
+            attempt.id,
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        for record in superseded {
+            self.cancel_attempt(record.id).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether [`RunnerManager::shutdown`] was called, nothing new should be submitted then.
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::Relaxed)
     }
 
-    pub fn run_from_blueprint(&self, blueprint: NSJailBlueprint, test_id: Uuid, attempt_id: Uuid) {
-        let instance = Instance::new(blueprint);
-        instance.spawn(self.db.clone(), test_id, attempt_id);
+    /// Stops handing out jobs and waits up to `--shutdown-timeout` for the running ones.
+    ///
+    /// Jobs still running after that are killed and put back into the queue, without the
+    /// interrupted run counting as one of their tries.
+    pub async fn shutdown(&self) {
+        self.shutting_down.store(true, Ordering::Relaxed);
+        // idle slots and waiting remote claims notice the shutdown once woken up
+        self.available.notify_waiters();
+
+        let grace = Duration::from_secs(self.config.shutdown_timeout);
+
+        if tokio::time::timeout(grace, self.wait_idle()).await.is_ok() {
+            return;
+        }
+
+        {
+            let running = self.running.lock().unwrap_or_else(PoisonError::into_inner);
+
+            eprintln!(
+                "Killing {} jobs still running after {grace:?}",
+                running.len()
+            );
+
+            for job in running.values() {
+                job.cancel.send_replace(true);
+            }
+        }
+
+        if tokio::time::timeout(KILL_TIMEOUT, self.wait_idle())
+            .await
+            .is_err()
+        {
+            eprintln!("Some killed jobs were not requeued, they are requeued on the next start");
+        }
+    }
+
+    /// Waits until no job runs in this process.
+    async fn wait_idle(&self) {
+        loop {
+            let idle = self.idle.notified();
+
+            if self
+                .running
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .is_empty()
+            {
+                return;
+            }
+
+            idle.await;
+        }
+    }
+
+    /// Receives every [`RunnerEvent`] from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<RunnerEvent> {
+        self.events.subscribe()
+    }
+
+    fn publish(&self, attempt: &Attempt, runner_id: Option<Uuid>, stage: RunStage) {
+        // fails only when nobody is listening
+        _ = self.events.send(RunnerEvent {
+            assignment_id: attempt.assignment_id,
+            attempt_id: attempt.id,
+            user_id: attempt.user_id,
+            runner_id,
+            stage,
+        });
+    }
+
+    /// Publishes the current stage of whatever the job runs.
+    async fn publish_job(&self, job: &Job) {
+        if self.events.receiver_count() == 0 {
+            return;
+        }
+
+        if let Err(err) = self.try_publish_job(job).await {
+            eprintln!("Failed to publish progress of job {}: {err:?}", job.id);
+        }
+    }
+
+    async fn try_publish_job(&self, job: &Job) -> sqlx::Result<()> {
+        let attempt = Attempt::by_id(&self.db, job.attempt_id).await?;
+
+        if let Some(id) = job.compilation_id {
+            let compilation = Compilation::by_id(&self.db, id).await?;
+
+            let stage = match (compilation.started_at, compilation.finished_at) {
+                (_, Some(_)) => RunStage::Finished {
+                    verdict: None,
+                    points: 0,
+                },
+                (Some(_), None) => RunStage::Started,
+                (None, None) => RunStage::Queued,
+            };
+
+            self.publish(&attempt, None, stage);
+        }
+
+        if let Some(id) = job.runner_id {
+            self.publish_runner(&attempt, &Runner::by_id(&self.db, id).await?);
+        }
+
+        Ok(())
+    }
+
+    /// Publishes the runners of the job's attempt as finished with `verdict` and no points.
+    async fn publish_failed(&self, job: &Job, runner_ids: &[Uuid], verdict: Verdict) {
+        if runner_ids.is_empty() || self.events.receiver_count() == 0 {
+            return;
+        }
+
+        let attempt = match Attempt::by_id(&self.db, job.attempt_id).await {
+            Ok(attempt) => attempt,
+            Err(err) => {
+                eprintln!("Failed to publish progress of job {}: {err:?}", job.id);
+                return;
+            }
+        };
+
+        for runner_id in runner_ids {
+            let stage = RunStage::Finished {
+                verdict: Some(verdict),
+                points: 0,
+            };
+
+            self.publish(&attempt, Some(*runner_id), stage);
+        }
+    }
+
+    fn publish_runner(&self, attempt: &Attempt, runner: &Runner) {
+        let stage = match (runner.started_at, runner.finished_at) {
+            (_, Some(_)) => RunStage::Finished {
+                verdict: runner.verdict,
+                points: runner.points,
+            },
+            (Some(_), None) => RunStage::Started,
+            (None, None) => RunStage::Queued,
+        };
+
+        self.publish(attempt, Some(runner.id), stage);
+    }
+
+    /// Position in the queue of every attempt that still has something waiting.
+    pub async fn queue_positions(&self) -> sqlx::Result<HashMap<Uuid, usize>> {
+        Job::queue_positions(&self.db).await
+    }
+
+    /// Claims a job for the `evaltor-worker` called `worker`, waiting up to `wait` for one.
+    ///
+    /// Jobs that turn out to have nothing left to run are settled right here.
+    pub async fn claim_remote(
+        &self,
+        worker: &str,
+        wait: Duration,
+    ) -> sqlx::Result<Option<WorkItem>> {
+        let deadline = tokio::time::Instant::now() + wait;
+
+        loop {
+            // registered before looking, a job queued or a shutdown started in between still
+            // wakes us up
+            let notified = self.available.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if self.is_shutting_down() {
+                return Ok(None);
+            }
+
+            let Some(job) = Job::claim(&self.db, LEASE, Some(worker)).await? else {
+                if tokio::time::timeout_at(deadline, notified).await.is_err() {
+                    return Ok(None);
+                }
+
+                continue;
+            };
+
+            match self.work_item(&job).await {
+                Ok(Some(item)) => return Ok(Some(item)),
+                Ok(None) => self.settle(&job, Ok(())).await,
+                Err(reason) => self.settle(&job, Err(reason)).await,
+            }
+        }
+    }
+
+    /// What a remote worker has to run for the job, `None` if it is already done.
+    async fn work_item(&self, job: &Job) -> Result<Option<WorkItem>, String> {
+        check_tries(job)?;
+
+        let language = Language::for_attempt(&self.db, job.attempt_id)
+            .await
+            .map_err(|err| format!("failed to fetch language: {err}"))?;
+
+        match (job.kind, job.compilation_id, job.runner_id) {
+            (JobKind::Compile, Some(compilation_id), _) => {
+                let compilation = Compilation::by_id(&self.db, compilation_id)
+                    .await
+                    .map_err(|err| format!("failed to fetch compilation: {err}"))?;
+
+                if compilation.finished_at.is_some() {
+                    self.after_compile(job, compilation.succeeded).await?;
+                    return Ok(None);
+                }
+
+                let blueprint =
+                    evaluation::compilation_blueprint(&self.db, &self.config, &compilation)
+                        .await
+                        .map_err(|err| format!("failed to build compilation blueprint: {err}"))?
+                        .ok_or("language of the attempt is not compiled")?;
+
+                compilation
+                    .mark_started(&self.db)
+                    .await
+                    .map_err(|err| format!("failed to mark compilation as started: {err}"))?;
+                self.publish_job(job).await;
+
+                Ok(Some(WorkItem::new(
+                    job,
+                    compilation.id,
+                    language.rootfs,
+                    &blueprint,
+                )))
+            }
+            (JobKind::Test, _, Some(runner_id)) => {
+                let runner = Runner::by_id(&self.db, runner_id)
+                    .await
+                    .map_err(|err| format!("failed to fetch runner: {err}"))?;
+
+                if runner.finished_at.is_some() {
+                    return Ok(None);
+                }
+
+                let blueprint = evaluation::runner_blueprint(&self.db, &self.config, &runner)
+                    .await
+                    .map_err(|err| format!("failed to build runner blueprint: {err}"))?;
+
+                runner
+                    .mark_started(&self.db)
+                    .await
+                    .map_err(|err| format!("failed to mark runner as started: {err}"))?;
+                self.publish_job(job).await;
+
+                Ok(Some(WorkItem::new(
+                    job,
+                    runner.id,
+                    language.rootfs,
+                    &blueprint,
+                )))
+            }
+            _ => Err("job has nothing to run".to_owned()),
+        }
+    }
+
+    /// Files a remote worker needs for the running job, `None` if there is no such job.
+    pub async fn remote_files(&self, job_id: Uuid) -> io::Result<Option<WorkFiles>> {
+        let Some(job) = Job::by_id(&self.db, job_id)
+            .await
+            .map_err(io::Error::other)?
+        else {
+            return Ok(None);
+        };
+
+        let attempt = Attempt::by_id(&self.db, job.attempt_id)
+            .await
+            .map_err(io::Error::other)?;
+        let dir = AttemptDir::new(&self.config.submissions, &attempt);
+
+        let stdin = match job.runner_id {
+            Some(runner_id) => {
+                let runner = Runner::by_id(&self.db, runner_id)
+                    .await
+                    .map_err(io::Error::other)?;
+
+                nsjail::test_input(&self.db, &self.config.tests, &runner)
+                    .await
+                    .map_err(io::Error::other)?
+            }
+            None => Vec::new(),
+        };
+
+        Ok(Some(WorkFiles {
+            workspace: worker::read_files(&dir.workspace).await?,
+            stdin,
+        }))
+    }
+
+    /// Extends the lease of a job run remotely, returns whether it should keep running.
+    ///
+    /// A job that was cancelled, or claimed again by someone else, should not.
+    pub async fn heartbeat_remote(&self, job_id: Uuid, tries: i64) -> sqlx::Result<bool> {
+        match Job::by_id(&self.db, job_id).await? {
+            Some(job) if job.status == JobStatus::Running && job.tries == tries => {
+                job.heartbeat(&self.db, LEASE).await?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Judges and records what a remote worker reported for the job.
+    ///
+    /// Returns `false` if the report is stale, the job was cancelled or claimed again since.
+    pub async fn complete_remote(&self, job_id: Uuid, result: WorkResult) -> sqlx::Result<bool> {
+        let Some(job) = Job::by_id(&self.db, job_id).await? else {
+            return Ok(false);
+        };
+
+        if job.status != JobStatus::Running || job.tries != result.tries {
+            return Ok(false);
+        }
+
+        let outcome = match result.output {
+            Ok(output) => self.record_remote(&job, output, &result.build).await,
+            Err(reason) => Err(reason),
+        };
+
+        self.settle(&job, outcome).await;
+
+        Ok(true)
+    }
+
+    async fn record_remote(
+        &self,
+        job: &Job,
+        output: SandboxOutput,
+        build: &[WorkFile],
+    ) -> Result<(), String> {
+        match (job.kind, job.compilation_id, job.runner_id) {
+            (JobKind::Compile, Some(compilation_id), _) => {
+                let compilation = Compilation::by_id(&self.db, compilation_id)
+                    .await
+                    .map_err(|err| format!("failed to fetch compilation: {err}"))?;
+
+                let attempt = Attempt::by_id(&self.db, job.attempt_id)
+                    .await
+                    .map_err(|err| format!("failed to fetch attempt: {err}"))?;
+                let build_dir = AttemptDir::new(&self.config.submissions, &attempt).build();
+
+                // the build may have been retried, only the output of this run counts
+                _ = tokio::fs::remove_dir_all(&build_dir).await;
+
+                worker::write_files(&build_dir, build)
+                    .await
+                    .map_err(|err| format!("failed to store build output: {err}"))?;
+
+                let succeeded = nsjail::record_compilation(&self.db, compilation, output).await?;
+
+                self.after_compile(job, succeeded).await
+            }
+            (JobKind::Test, _, Some(runner_id)) => {
+                let runner = Runner::by_id(&self.db, runner_id)
+                    .await
+                    .map_err(|err| format!("failed to fetch runner: {err}"))?;
+
+                nsjail::record_test(&self.db, &self.config.tests, runner, output).await
+            }
+            _ => Err("job has nothing to run".to_owned()),
+        }
+    }
+
+    async fn slot(self, cpu: Option<usize>) {
+        while !self.is_shutting_down() {
+            // registered before looking, like in claim_remote
+            let notified = self.available.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            match Job::claim(&self.db, LEASE, None).await {
+                Ok(Some(job)) => {
+                    let cancel = self.track(&job);
+
+                    let result = tokio::select! {
+                        result = self.run(&job, cpu, cancel.clone()) => result,
+                        () = self.heartbeat(&job) => Ok(()),
+                    };
+
+                    // cancel_attempt already finished everything the job was running
+                    if !*cancel.borrow() {
+                        self.settle(&job, result).await;
+                    } else if self.is_shutting_down() {
+                        self.interrupt(&job).await;
+                    }
+
+                    self.running
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .remove(&job.id);
+                    self.idle.notify_waiters();
+                }
+                Ok(None) => {
+                    _ = tokio::time::timeout(POLL_INTERVAL, notified).await;
+                }
+                Err(err) => {
+                    eprintln!("Failed to claim job: {err:?}");
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                }
+            }
+        }
+    }
+
+    /// Registers the job as running, so it can be cancelled.
+    fn track(&self, job: &Job) -> CancelSignal {
+        let (cancel, signal) = watch::channel(false);
+
+        self.running
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(
+                job.id,
+                RunningJob {
+                    attempt_id: job.attempt_id,
+                    cancel,
+                },
+            );
+
+        signal
+    }
+
+    /// Keeps the lease of the job alive, never returns.
+    async fn heartbeat(&self, job: &Job) {
+        loop {
+            tokio::time::sleep(HEARTBEAT_INTERVAL).await;
+
+            if let Err(err) = job.heartbeat(&self.db, LEASE).await {
+                eprintln!("Failed to extend job lease: {err:?}");
+            }
+        }
+    }
+
+    /// Finishes the job, or retries it if it failed and has tries left.
+    async fn settle(&self, job: &Job, result: Result<(), String>) {
+        let result = match result {
+            Ok(()) => job.finish(&self.db).await,
+            Err(reason) if job.tries < MAX_TRIES => {
+                eprintln!(
+                    "Job {} failed on try {}, retrying: {reason}",
+                    job.id, job.tries
+                );
+                self.record_error(job, &reason, false).await;
+                job.requeue(&self.db).await
+            }
+            Err(reason) => {
+                eprintln!(
+                    "Job {} failed on try {}, giving up: {reason}",
+                    job.id, job.tries
+                );
+                self.record_error(job, &reason, true).await;
+                job.finish(&self.db).await
+            }
+        };
+
+        if let Err(err) = result {
+            eprintln!("Failed to update job {}: {err:?}", job.id);
+        }
+
+        self.publish_job(job).await;
+    }
+
+    /// Puts a job killed by a shutdown back into the queue, as if it never ran.
+    async fn interrupt(&self, job: &Job) {
+        let result = async {
+            // a job cancelled meanwhile stays cancelled
+            if !job.requeue_interrupted(&self.db).await? {
+                return Ok(());
+            }
+
+            match (job.compilation_id, job.runner_id) {
+                (Some(id), _) => {
+                    Compilation::requeue_after_error(&self.db, id, SHUTDOWN_REASON).await
+                }
+                (_, Some(id)) => Runner::requeue_after_error(&self.db, id, SHUTDOWN_REASON).await,
+                (None, None) => Ok(()),
+            }
+        };
+
+        if let Err(err) = result.await {
+            eprintln!("Failed to requeue job {}: {err:?}", job.id);
+        }
+    }
+
+    /// Stores why the job failed on what it runs, as a system error once it is `terminal`.
+    async fn record_error(&self, job: &Job, reason: &str, terminal: bool) {
+        let result = match (job.compilation_id, job.runner_id) {
+            (Some(id), _) if terminal => self.give_up_compilation(job, id, reason).await,
+            (Some(id), _) => Compilation::requeue_after_error(&self.db, id, reason).await,
+            (_, Some(id)) if terminal => Runner::finish_system_error(&self.db, id, reason).await,
+            (_, Some(id)) => Runner::requeue_after_error(&self.db, id, reason).await,
+            (None, None) => Ok(()),
+        };
+
+        if let Err(err) = result {
+            eprintln!("Failed to record error of job {}: {err:?}", job.id);
+        }
+    }
+
+    /// Fails the compilation and every test of the attempt waiting for it.
+    async fn give_up_compilation(&self, job: &Job, id: Uuid, reason: &str) -> sqlx::Result<()> {
+        Compilation::finish_system_error(&self.db, id, reason).await?;
+
+        let reason = format!("compilation could not be run: {reason}");
+
+        let mut failed = Vec::new();
+
+        for runner in Runner::unqueued_for_attempt(&self.db, job.attempt_id).await? {
+            Runner::finish_system_error(&self.db, runner.id, &reason).await?;
+            failed.push(runner.id);
+        }
+
+        self.publish_failed(job, &failed, Verdict::SystemError)
+            .await;
+
+        Ok(())
+    }
+
+    async fn run(&self, job: &Job, cpu: Option<usize>, cancel: CancelSignal) -> Result<(), String> {
+        check_tries(job)?;
+
+        match (job.kind, job.compilation_id, job.runner_id) {
+            (JobKind::Compile, Some(compilation_id), _) => {
+                let compilation = Compilation::by_id(&self.db, compilation_id)
+                    .await
+                    .map_err(|err| format!("failed to fetch compilation: {err}"))?;
+
+                self.compile(job, compilation, cpu, cancel).await
+            }
+            (JobKind::Test, _, Some(runner_id)) => {
+                let runner = Runner::by_id(&self.db, runner_id)
+                    .await
+                    .map_err(|err| format!("failed to fetch runner: {err}"))?;
+
+                self.test(job, runner, cpu, cancel).await
+            }
+            _ => Err("job has nothing to run".to_owned()),
+        }
+    }
+
+    async fn compile(
+        &self,
+        job: &Job,
+        compilation: Compilation,
+        cpu: Option<usize>,
+        cancel: CancelSignal,
+    ) -> Result<(), String> {
+        // a retried job may find its compilation already done by the worker that crashed
+        let succeeded = if compilation.finished_at.is_some() {
+            compilation.succeeded
+        } else {
+            let mut blueprint =
+                evaluation::compilation_blueprint(&self.db, &self.config, &compilation)
+                    .await
+                    .map_err(|err| format!("failed to build compilation blueprint: {err}"))?
+                    .ok_or("language of the attempt is not compiled")?;
+
+            blueprint.cpu = cpu;
+
+            compilation
+                .mark_started(&self.db)
+                .await
+                .map_err(|err| format!("failed to mark compilation as started: {err}"))?;
+            self.publish_job(job).await;
+
+            Instance::new(blueprint, self.sandbox.clone(), cancel)
+                .compile(&self.db, compilation)
+                .await?
+        };
+
+        self.after_compile(job, succeeded).await
+    }
+
+    /// Queues the tests waiting for the build, or fails them all if it did not succeed.
+    async fn after_compile(&self, job: &Job, succeeded: bool) -> Result<(), String> {
+        let runners = Runner::unqueued_for_attempt(&self.db, job.attempt_id)
+            .await
+            .map_err(|err| format!("failed to fetch runners of attempt: {err}"))?;
+
+        let mut failed = Vec::new();
+
+        for runner in runners {
+            if succeeded {
+                // the tests take the place of the compilation in the queue
+                Job::test(&runner, job.user_id, job.lane, job.queued_at)
+                    .insert_new(&self.db)
+                    .await
+            } else {
+                failed.push(runner.id);
+                runner.finish_early(&self.db, Verdict::CompileError).await
+            }
+            .map_err(|err| format!("failed to queue runner after compilation: {err}"))?;
+        }
+
+        self.available.notify_waiters();
+        self.publish_failed(job, &failed, Verdict::CompileError)
+            .await;
+
+        Ok(())
+    }
+
+    async fn test(
+        &self,
+        job: &Job,
+        runner: Runner,
+        cpu: Option<usize>,
+        cancel: CancelSignal,
+    ) -> Result<(), String> {
+        if runner.finished_at.is_some() {
+            return Ok(());
+        }
+
+        let mut blueprint = evaluation::runner_blueprint(&self.db, &self.config, &runner)
+            .await
+            .map_err(|err| format!("failed to build runner blueprint: {err}"))?;
+
+        blueprint.cpu = cpu;
+
+        runner
+            .mark_started(&self.db)
+            .await
+            .map_err(|err| format!("failed to mark runner as started: {err}"))?;
+        self.publish_job(job).await;
+
+        Instance::new(blueprint, self.sandbox.clone(), cancel)
+            .run_test(&self.db, runner)
+            .await
+    }
+}
+
+/// Refuses jobs that every worker so far crashed on without reporting back.
+fn check_tries(job: &Job) -> Result<(), String> {
+    if job.tries > MAX_TRIES {
+        return Err(format!("abandoned after {} tries", job.tries - 1));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::SqlitePool;
+    use uuid::Uuid;
+
+    use super::{LEASE, RunnerManager};
+    use crate::{
+        models::{Attempt, Compilation, CompilationOutcome, Job, JobKind, JobLane, Runner},
+        testing,
+    };
+
+    /// A C attempt with one test, whose build ended with `outcome`.
+    async fn built_attempt(db: &SqlitePool, outcome: CompilationOutcome) -> (Attempt, Uuid) {
+        let student = testing::user(db, "student").await;
+        let assignment_id = testing::assignment(db).await;
+        let test_id = testing::test(db, assignment_id, 1).await;
+        let mut attempt = testing::attempt(db, assignment_id, &student).await;
+
+        sqlx::query!(
+            "UPDATE attempts SET language_id = ? WHERE id = ?",
+            testing::C,
+            attempt.id,
+        )
+        .execute(db)
+        .await
+        .expect("language changed");
+        attempt.language_id = testing::C;
+
+        let compilation = testing::blueprint().pending_compilation(attempt.id);
+        compilation
+            .insert_new(db)
+            .await
+            .expect("compilation inserted");
+
+        let now = chrono::Utc::now().naive_utc();
+        sqlx::query!(
+            "UPDATE compilations SET finished_at = ?, outcome = ? WHERE id = ?",
+            now,
+            outcome,
+            compilation.id,
+        )
+        .execute(db)
+        .await
+        .expect("compilation finished");
+
+        (attempt, test_id)
+    }
+
+    async fn manager(db: &SqlitePool) -> RunnerManager {
+        let args = testing::args(&[]);
+
+        RunnerManager::start(db.clone(), args.sandbox.build(), args)
+            .await
+            .expect("manager started")
+    }
+
+    #[tokio::test]
+    async fn rejudged_test_is_built_again_after_a_system_error() {
+        let db = testing::db().await;
+        let (attempt, test_id) = built_attempt(&db, CompilationOutcome::SystemError).await;
+
+        manager(&db)
+            .await
+            .rejudge_test(&attempt, test_id)
+            .await
+            .expect("test rejudged");
+
+        let build = Compilation::latest_for_attempt(&db, attempt.id)
+            .await
+            .expect("compilation fetched")
+            .expect("compilation exists");
+        assert_eq!((build.finished_at, build.outcome), (None, None));
+
+        let job = Job::claim(&db, LEASE, None)
+            .await
+            .expect("claim ran")
+            .expect("build queued");
+        assert_eq!(
+            (job.kind, job.lane, job.compilation_id),
+            (JobKind::Compile, JobLane::Rejudge, Some(build.id))
+        );
+
+        // the test waits for the build
+        let waiting = Runner::unqueued_for_attempt(&db, attempt.id)
+            .await
+            .expect("runners fetched");
+        assert_eq!(waiting.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn rejudged_test_of_a_failed_build_is_left_alone() {
+        let db = testing::db().await;
+        let (attempt, test_id) = built_attempt(&db, CompilationOutcome::Failed).await;
+
+        manager(&db)
+            .await
+            .rejudge_test(&attempt, test_id)
+            .await
+            .expect("test rejudged");
+
+        assert!(
+            Job::claim(&db, LEASE, None)
+                .await
+                .expect("claim ran")
+                .is_none()
+        );
+        assert!(
+            Runner::unqueued_for_attempt(&db, attempt.id)
+                .await
+                .expect("runners fetched")
+                .is_empty()
+        );
     }
 }
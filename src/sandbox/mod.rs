@@ -0,0 +1,192 @@
+use std::ffi::OsStr;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::process::Output;
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::{Args, ValueEnum};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::watch;
+use uuid::Uuid;
+
+use crate::cgroup::ResourceUsage;
+use crate::nsjail::{NSJailBlueprint, NsjailPrivilege, NsjailSandbox};
+use crate::worker::base64_bytes;
+
+pub use process::ProcessSandbox;
+
+mod process;
+
+/// Result of a single sandboxed run.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SandboxOutput {
+    /// Exit code of the program, `128 + signal` if it was killed, `None` if the sandbox itself
+    /// was killed.
+    pub exit_code: Option<i32>,
+    #[serde(with = "base64_bytes")]
+    pub stdout: Vec<u8>,
+    #[serde(with = "base64_bytes")]
+    pub stderr: Vec<u8>,
+    pub usage: ResourceUsage,
+    /// Backend specific description of the sandbox, kept with the run for auditing
+    pub config: Option<String>,
+    /// What the sandbox reported if it failed to run the program to the end itself
+    #[serde(default)]
+    pub sandbox_error: Option<String>,
+}
+
+impl SandboxOutput {
+    /// Exit code of the program, `None` if the sandbox failed to run it or was killed.
+    pub fn program_exit_code(&self) -> Option<i32> {
+        self.exit_code.filter(|_| self.sandbox_error.is_none())
+    }
+}
+
+/// Turns `true` once the run should be stopped early.
+pub type CancelSignal = watch::Receiver<bool>;
+
+/// How long a cancelled run gets to exit after `SIGTERM` before it is killed.
+const CANCEL_GRACE: Duration = Duration::from_secs(2);
+
+pub type SandboxFuture<'a> = Pin<Box<dyn Future<Output = io::Result<SandboxOutput>> + Send + 'a>>;
+
+/// Backend that executes blueprints in isolation.
+pub trait Sandbox: fmt::Debug + Send + Sync {
+    /// Runs the blueprint to completion, feeding it `stdin` if the blueprint asks for it.
+    ///
+    /// `run_id` identifies the run for any per-run resources the backend has to create. Once
+    /// `cancel` fires the run is killed and fails with [`io::ErrorKind::Interrupted`].
+    fn run(
+        &self,
+        blueprint: NSJailBlueprint,
+        run_id: Uuid,
+        stdin: Vec<u8>,
+        cancel: CancelSignal,
+    ) -> SandboxFuture<'_>;
+}
+
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum SandboxKind {
+    /// nsjail with namespaces and cgroup v2 limits
+    #[default]
+    Nsjail,
+    /// Plain child processes limited only by rlimits and a timeout. NOT safe for production,
+    /// submissions can read and write anything the server can.
+    Process,
+}
+
+/// How submissions are sandboxed, shared by the server and `evaltor-worker`.
+#[derive(Clone, Debug, Args)]
+pub struct SandboxArgs {
+    /// Sandbox backend used to run submissions
+    #[clap(long = "sandbox", env = "EVALTOR_SANDBOX", value_enum, default_value_t)]
+    pub kind: SandboxKind,
+
+    /// Directory holding a root filesystem for every language, e.g. unpacked OCI images
+    #[clap(
+        long,
+        env = "EVALTOR_ROOTFS",
+        default_value = "/var/lib/evaltor/rootfs"
+    )]
+    pub rootfs: PathBuf,
+
+    /// Path to the nsjail binary
+    #[clap(long, env = "EVALTOR_NSJAIL_PATH", default_value = "nsjail")]
+    pub nsjail_path: PathBuf,
+
+    /// How nsjail obtains the privileges to create the jail
+    #[clap(long, env = "EVALTOR_NSJAIL_PRIVILEGE", value_enum, default_value_t)]
+    pub nsjail_privilege: NsjailPrivilege,
+
+    /// Delegated cgroup v2 directory under which every sandboxed run gets its own cgroup
+    #[clap(
+        long,
+        env = "EVALTOR_CGROUP_ROOT",
+        default_value = "/sys/fs/cgroup/evaltor"
+    )]
+    pub cgroup_root: PathBuf,
+
+    /// Maximum number of processes and threads inside a single sandbox
+    #[clap(long, env = "EVALTOR_PIDS_LIMIT", default_value_t = 64)]
+    pub pids_limit: i64,
+}
+
+impl SandboxArgs {
+    pub fn build(&self) -> Arc<dyn Sandbox> {
+        match self.kind {
+            SandboxKind::Nsjail => Arc::new(NsjailSandbox {
+                nsjail_path: self.nsjail_path.clone(),
+                privilege: self.nsjail_privilege,
+                cgroup_root: self.cgroup_root.clone(),
+                pids_limit: self.pids_limit,
+            }),
+            SandboxKind::Process => {
+                eprintln!(
+                    "WARNING: using the process sandbox, submissions run without any isolation. Never use this in production."
+                );
+                Arc::new(ProcessSandbox)
+            }
+        }
+    }
+}
+
+/// Spawns the command, writes `stdin` into it if it was piped and collects its output.
+///
+/// If `cancel` fires first, the child gets `SIGTERM`, which `sudo` passes on to nsjail so it
+/// tears the whole jail down, and is killed if it does not exit in time.
+pub async fn communicate(
+    mut cmd: Command,
+    stdin: &[u8],
+    mut cancel: CancelSignal,
+) -> io::Result<Output> {
+    cmd.kill_on_drop(true);
+
+    let mut child = cmd.spawn()?;
+    let pid = child.id();
+
+    if let Some(mut pipe) = child.stdin.take() {
+        _ = pipe.write_all(stdin).await;
+    }
+
+    let output = child.wait_with_output();
+    tokio::pin!(output);
+
+    tokio::select! {
+        output = &mut output => return output,
+        Ok(_) = cancel.wait_for(|cancelled| *cancelled) => {}
+    }
+
+    if let Some(pid) = pid {
+        _ = Command::new("kill")
+            .arg("-TERM")
+            .arg(pid.to_string())
+            .status()
+            .await;
+    }
+
+    // dropping the output future kills the child if it is still around
+    _ = tokio::time::timeout(CANCEL_GRACE, output).await;
+
+    Err(io::Error::new(
+        io::ErrorKind::Interrupted,
+        "run was cancelled",
+    ))
+}
+
+/// Command running `program`, pinned to `cpu` through `taskset` if one is given.
+pub fn pinned_command(program: impl AsRef<OsStr>, cpu: Option<usize>) -> Command {
+    match cpu {
+        Some(cpu) => {
+            let mut cmd = Command::new("taskset");
+            cmd.arg("--cpu-list").arg(cpu.to_string()).arg(program);
+            cmd
+        }
+        None => Command::new(program),
+    }
+}
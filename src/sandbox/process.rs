@@ -0,0 +1,102 @@
+use std::io;
+use std::os::unix::process::ExitStatusExt;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+use crate::cgroup::ResourceUsage;
+use crate::nsjail::{BuildMount, NSJailBlueprint};
+use crate::sandbox::{
+    CancelSignal, Sandbox, SandboxFuture, SandboxOutput, communicate, pinned_command,
+};
+
+/// Runs blueprints as ordinary child processes of the server.
+///
+/// Only the time limit (as a wall clock timeout and `ulimit -t`) and the memory limit (as
+/// `ulimit -v`) are enforced. There is no filesystem, network or process isolation at all and
+/// the language root filesystems are ignored in favour of the host's, so this backend exists
+/// purely for local development and tests.
+#[derive(Debug, Default)]
+pub struct ProcessSandbox;
+
+/// Sets the rlimits and replaces itself with the actual command, passed as positional arguments.
+const ULIMIT_SCRIPT: &str = r#"ulimit -v "$1" && ulimit -t "$2" && shift 2 && exec "$@""#;
+
+impl Sandbox for ProcessSandbox {
+    fn run(
+        &self,
+        blueprint: NSJailBlueprint,
+        _run_id: Uuid,
+        stdin: Vec<u8>,
+        cancel: CancelSignal,
+    ) -> SandboxFuture<'_> {
+        Box::pin(async move {
+            // there are no mounts, so the build directory has to be where the commands expect it
+            if let Some(BuildMount::ReadOnly(path) | BuildMount::Writable(path)) =
+                &blueprint.build_dir
+                && *path != blueprint.mountpoint.join("build")
+            {
+                return Err(io::Error::other(
+                    "process sandbox requires the build directory inside the workspace",
+                ));
+            }
+
+            let mut cmd = pinned_command("sh", blueprint.cpu);
+
+            cmd.current_dir(&blueprint.mountpoint)
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .stdin(if blueprint.write_stdin {
+                    Stdio::piped()
+                } else {
+                    Stdio::null()
+                })
+                .arg("-c")
+                .arg(ULIMIT_SCRIPT)
+                .arg("sh")
+                .arg(blueprint.memory_limit.saturating_mul(1024).to_string())
+                .arg(blueprint.time_limit.to_string())
+                .args(blueprint.command.split_ascii_whitespace());
+
+            let timeout = Duration::from_secs(u64::try_from(blueprint.time_limit).unwrap_or(0));
+            let started = Instant::now();
+
+            let output = match tokio::time::timeout(timeout, communicate(cmd, &stdin, cancel)).await
+            {
+                Ok(output) => output?,
+                // dropping the future kills the child, report it the way nsjail reports SIGKILL
+                Err(_) => {
+                    return Ok(SandboxOutput {
+                        exit_code: Some(128 + 9),
+                        stdout: Vec::new(),
+                        stderr: Vec::new(),
+                        usage: ResourceUsage {
+                            wall_time: started.elapsed(),
+                            ..ResourceUsage::default()
+                        },
+                        config: None,
+                        sandbox_error: None,
+                    });
+                }
+            };
+
+            let exit_code = output
+                .status
+                .code()
+                .or_else(|| output.status.signal().map(|signal| 128 + signal));
+
+            Ok(SandboxOutput {
+                exit_code,
+                stdout: output.stdout,
+                stderr: output.stderr,
+                usage: ResourceUsage {
+                    wall_time: started.elapsed(),
+                    ..ResourceUsage::default()
+                },
+                config: None,
+                sandbox_error: None,
+            })
+        })
+    }
+}
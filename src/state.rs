@@ -1,17 +1,17 @@
-use std::fmt;
+use std::{fmt, sync::Arc};
 
 use axum::extract::FromRef;
 use sqlx::SqlitePool;
 
 use crate::EvaltorArgs;
-use crate::auth::DiscoveredClient;
+use crate::auth::OidcProvider;
 use crate::runner_manager::RunnerManager;
 
 #[derive(Clone)]
 pub struct EvaltorState {
     pub db_pool: SqlitePool,
     pub runner_manager: RunnerManager,
-    pub oidc_client: DiscoveredClient,
+    pub oidc_providers: Arc<[OidcProvider]>,
     pub config: EvaltorArgs,
 }
 
@@ -1,6 +1,9 @@
 use askama::Template;
 
-use crate::{filters, models::Assignment};
+use crate::{
+    filters,
+    models::{Assignment, Language},
+};
 
 #[derive(Template)]
 #[template(path = "assignment.html")]
@@ -8,4 +11,5 @@ pub struct AssignmentPage {
     pub user_name: String,
     pub user_email: String,
     pub assignment: Assignment,
+    pub languages: Vec<Language>,
 }
@@ -1,12 +1,26 @@
 use askama::Template;
 use uuid::Uuid;
 
-use crate::models::Attempt;
+use crate::models::{Attempt, SubmissionQuota};
 
 #[derive(Template)]
 #[template(path = "partials/attempts.html")]
 #[expect(dead_code)]
 pub struct AttemptsPartial {
     pub assignment_id: Uuid,
-    pub attempts: Vec<Attempt>,
+    pub attempts: Vec<ListedAttempt>,
+    pub quota: SubmissionQuota,
+    /// Why the attempt that was just submitted got turned down
+    pub rejection: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct ListedAttempt {
+    pub attempt: Attempt,
+    /// Counts the attempts of its author from 1
+    pub number: usize,
+    /// Name of the author, `None` for the attempts of whoever is looking
+    pub author: Option<String>,
+    /// Position in the judge queue, if it is still waiting
+    pub queue_position: Option<usize>,
 }
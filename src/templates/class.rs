@@ -1,9 +1,10 @@
 use askama::Template;
+use chrono::NaiveDateTime;
 use uuid::Uuid;
 
 use crate::{
     Points, filters,
-    models::{Assignment, Class, User},
+    models::{Assignment, Class, ClassRole, JobStatus, PendingEnrollment, Test, User},
 };
 
 #[derive(Template)]
@@ -11,10 +12,96 @@ use crate::{
 pub struct ClassPage {
     pub user_name: String,
     pub user_email: String,
-    pub user_id: Uuid,
+    /// Role of the user in the class, `None` if they are not a member
+    pub role: Option<ClassRole>,
     pub class: Class,
     pub assignments: Vec<(Assignment, Points)>,
     pub points: Points,
-    pub all_users: Vec<User>,
+    /// Students the assignments can be assigned to
+    pub students: Vec<User>,
+    pub all_assignments: Vec<Assignment>,
+    /// Assignments of the class with their tests, which can be rejudged
+    pub rejudge_targets: Vec<(Assignment, Vec<Test>)>,
+}
+
+impl ClassPage {
+    pub fn is_teacher(&self) -> bool {
+        self.role == Some(ClassRole::Teacher)
+    }
+
+    pub fn is_staff(&self) -> bool {
+        self.role.is_some_and(ClassRole::is_staff)
+    }
+}
+
+/// A run in the class that ended as a system error.
+pub struct SystemErrorRun {
+    pub runner_id: Uuid,
+    pub attempt_id: Uuid,
+    pub assignment_name: String,
+    pub test_name: String,
+    pub user_name: String,
+    pub user_email: String,
+    pub finished_at: Option<NaiveDateTime>,
+    pub error_reason: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "system_errors.html")]
+pub struct SystemErrorsPage {
+    pub user_name: String,
+    pub user_email: String,
+    pub class: Class,
+    pub runs: Vec<SystemErrorRun>,
+}
+
+/// A job of the class that is waiting or running.
+pub struct ActiveJob {
+    pub attempt_id: Uuid,
+    pub status: JobStatus,
+    pub tries: i64,
+    pub assignment_name: String,
+    /// `None` for the compilation of the attempt
+    pub test_name: Option<String>,
+    pub user_name: String,
+    pub user_email: String,
+    pub queued_at: NaiveDateTime,
+    pub claimed_at: Option<NaiveDateTime>,
+    /// `evaltor-worker` running the job, `None` if it runs on the server itself
+    pub worker: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "jobs.html")]
+pub struct JobsPage {
+    pub user_name: String,
+    pub user_email: String,
+    pub class: Class,
+    pub jobs: Vec<ActiveJob>,
+}
+
+/// A member of the class as listed on its roster.
+pub struct RosterMember {
+    pub user_id: Uuid,
+    pub name: String,
+    pub email: String,
+    pub student_id: Option<String>,
+    pub role: ClassRole,
+}
+
+#[derive(Template)]
+#[template(path = "roster.html")]
+pub struct RosterPage {
+    pub user_name: String,
+    pub user_email: String,
+    pub class: Class,
+    pub join_code: Option<String>,
+    pub members: Vec<RosterMember>,
+    /// Imported students who have not signed in yet
+    pub pending: Vec<PendingEnrollment>,
     pub all_assignments: Vec<Assignment>,
+    /// Other classes the teacher can move students to
+    pub other_classes: Vec<Class>,
+    /// Outcome of the roster import that was just made
+    pub import_message: Option<String>,
 }
@@ -4,6 +4,8 @@ mod class;
 mod runner;
 
 pub use assignment::AssignmentPage;
-pub use attempt::AttemptsPartial;
-pub use class::ClassPage;
-pub use runner::{RunnerResult, RunnersPartial};
+pub use attempt::{AttemptsPartial, ListedAttempt};
+pub use class::{
+    ActiveJob, ClassPage, JobsPage, RosterMember, RosterPage, SystemErrorRun, SystemErrorsPage,
+};
+pub use runner::{CompilationResult, RunnerResult, RunnersPartial};
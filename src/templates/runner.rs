@@ -2,24 +2,82 @@ use askama::Template;
 use chrono::NaiveDateTime;
 use uuid::Uuid;
 
+use crate::models::{Compilation, CompilationOutcome, NetworkPolicy, Verdict};
+
 pub struct RunnerResult {
+    pub id: Uuid,
     pub test_name: String,
     pub test_description: String,
+    pub started_at: Option<NaiveDateTime>,
     pub finished_at: Option<NaiveDateTime>,
     pub passed: bool,
+    pub verdict: Option<Verdict>,
     pub stdout: Option<String>,
     pub expected_stdout: Option<String>,
 
     pub test_points: i64,
     pub runner_points: i64,
+
+    pub memory_limit: i64,
+    pub time_limit: i64,
+    pub max_cpus: i64,
+    pub network_policy: NetworkPolicy,
+
+    pub wall_time_ms: Option<i64>,
+    pub cpu_time_ms: Option<i64>,
+    pub peak_memory_kb: Option<i64>,
+}
+
+pub struct CompilationResult {
+    pub started_at: Option<NaiveDateTime>,
+    pub finished_at: Option<NaiveDateTime>,
+    pub succeeded: bool,
+    pub cancelled: bool,
+    /// The build could not be run at all, its tests are system errors too
+    pub system_error: bool,
+    pub stderr: Option<String>,
+}
+
+impl From<Compilation> for CompilationResult {
+    fn from(compilation: Compilation) -> Self {
+        Self {
+            started_at: compilation.started_at,
+            finished_at: compilation.finished_at,
+            succeeded: compilation.succeeded,
+            cancelled: compilation.outcome == Some(CompilationOutcome::Cancelled),
+            system_error: compilation.outcome == Some(CompilationOutcome::SystemError),
+            stderr: compilation
+                .stderr
+                .map(|err| String::from_utf8_lossy(&err).into_owned()),
+        }
+    }
 }
 
 #[derive(Template)]
 #[template(path = "partials/runners.html")]
 pub struct RunnersPartial {
     pub attempt_id: Uuid,
+    pub compilation: Option<CompilationResult>,
     pub runners: Vec<RunnerResult>,
+    pub verdict_filter: Option<Verdict>,
+    /// Position of the attempt in the queue, swapped into the attempt's summary
+    pub queue_position: Option<usize>,
 
     pub total_test_points: i64,
     pub total_runner_points: i64,
 }
+
+impl RunnersPartial {
+    /// A failed build replaces the individual test results, which would all fail the same way.
+    ///
+    /// A build the sandbox failed to run is no fault of the program, the tests show that.
+    pub fn compile_failed(&self) -> bool {
+        self.compilation
+            .as_ref()
+            .is_some_and(|c| c.finished_at.is_some() && !c.succeeded && !c.system_error)
+    }
+
+    pub fn is_filtered_by(&self, verdict: Verdict) -> bool {
+        self.verdict_filter == Some(verdict)
+    }
+}
@@ -0,0 +1,216 @@
+//! Fixtures for tests that need a database, each test gets its own in-memory one.
+
+use std::str::FromStr;
+
+use chrono::Utc;
+use clap::Parser;
+use sqlx::{
+    SqlitePool,
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+};
+use uuid::Uuid;
+
+use crate::{
+    EvaltorArgs,
+    models::{Attempt, ClassMember, ClassRole, NetworkPolicy, Runner, User, UserAssignment},
+    nsjail::NSJailBlueprint,
+};
+
+pub mod oidc;
+
+/// Python 3, seeded by the migrations.
+pub const PYTHON: Uuid = Uuid::from_u128(0xde37_d083_ed29_45b8_bc59_b814_fbdd_7869);
+/// C compiled with gcc, seeded by the migrations.
+pub const C: Uuid = Uuid::from_u128(0x692a_d2a9_cf50_4f52_b74f_2d6a_bebb_4b76);
+
+/// Server configuration that judges nothing itself, with `extra` arguments on top.
+pub fn args(extra: &[&str]) -> EvaltorArgs {
+    let args = [
+        "evaltor",
+        "--submissions=/nonexistent/submissions",
+        "--tests=/nonexistent/tests",
+        "--hostname=http://localhost/",
+        "--port=0",
+        "--judge-workers=0",
+    ];
+
+    EvaltorArgs::try_parse_from(args.iter().chain(extra)).expect("valid arguments")
+}
+
+/// A migrated in-memory database.
+///
+/// The pool keeps its single connection forever, the database would be gone with it.
+pub async fn db() -> SqlitePool {
+    // migrations rebuild tables, like in `server`
+    let options = SqliteConnectOptions::from_str("sqlite::memory:")
+        .expect("valid database url")
+        .foreign_keys(false);
+
+    let db = SqlitePoolOptions::new()
+        .max_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect_with(options)
+        .await
+        .expect("in-memory database");
+
+    sqlx::migrate!("./migrations")
+        .run(&db)
+        .await
+        .expect("migrations run");
+
+    sqlx::query("PRAGMA foreign_keys = ON")
+        .execute(&db)
+        .await
+        .expect("foreign keys on");
+
+    db
+}
+
+pub async fn user(db: &SqlitePool, name: &str) -> User {
+    let user = User {
+        id: Uuid::new_v4(),
+        email: format!("{name}@example.com"),
+        name: name.to_owned(),
+        is_admin: false,
+        student_id: None,
+    };
+
+    sqlx::query!(
+        "INSERT INTO users (id, email, name, is_admin) VALUES (?, ?, ?, ?)",
+        user.id,
+        user.email,
+        user.name,
+        user.is_admin,
+    )
+    .execute(db)
+    .await
+    .expect("user inserted");
+
+    user
+}
+
+pub async fn admin(db: &SqlitePool, name: &str) -> User {
+    let mut user = user(db, name).await;
+
+    sqlx::query!("UPDATE users SET is_admin = true WHERE id = ?", user.id)
+        .execute(db)
+        .await
+        .expect("user made admin");
+
+    user.is_admin = true;
+    user
+}
+
+/// A class created by `teacher`, who teaches it.
+pub async fn class(db: &SqlitePool, teacher: &User) -> Uuid {
+    let id = Uuid::new_v4();
+
+    sqlx::query!(
+        "INSERT INTO classes (id, creator_id, name, description) VALUES (?, ?, 'Class', '')",
+        id,
+        teacher.id,
+    )
+    .execute(db)
+    .await
+    .expect("class inserted");
+
+    member(db, id, teacher, ClassRole::Teacher).await;
+
+    id
+}
+
+pub async fn member(db: &SqlitePool, class_id: Uuid, user: &User, role: ClassRole) {
+    ClassMember::add(db, class_id, user.id, role)
+        .await
+        .expect("member added");
+}
+
+pub async fn assignment(db: &SqlitePool) -> Uuid {
+    let id = Uuid::new_v4();
+
+    sqlx::query!(
+        "INSERT INTO assignments (id, name, description) VALUES (?, 'Assignment', '')",
+        id,
+    )
+    .execute(db)
+    .await
+    .expect("assignment inserted");
+
+    id
+}
+
+/// Assigns the assignment to the student in the class, enrolling them first.
+pub async fn assign(db: &SqlitePool, class_id: Uuid, assignment_id: Uuid, student: &User) {
+    member(db, class_id, student, ClassRole::Student).await;
+
+    UserAssignment::assign_to_student(db, student.id, assignment_id, class_id)
+        .await
+        .expect("assignment assigned");
+}
+
+pub async fn test(db: &SqlitePool, assignment_id: Uuid, points: i64) -> Uuid {
+    let id = Uuid::new_v4();
+
+    sqlx::query!(
+        "INSERT INTO tests (id, name, description, type, assignment_id, points) VALUES (?, ?, '', 'compare', ?, ?)",
+        id,
+        id,
+        assignment_id,
+        points,
+    )
+    .execute(db)
+    .await
+    .expect("test inserted");
+
+    id
+}
+
+pub async fn attempt(db: &SqlitePool, assignment_id: Uuid, user: &User) -> Attempt {
+    let attempt = Attempt {
+        id: Uuid::new_v4(),
+        assignment_id,
+        user_id: user.id,
+        language_id: PYTHON,
+        submitted_at: Utc::now().naive_utc(),
+    };
+
+    sqlx::query!(
+        "INSERT INTO attempts (id, assignment_id, user_id, language_id, submitted_at) VALUES (?, ?, ?, ?, ?)",
+        attempt.id,
+        attempt.assignment_id,
+        attempt.user_id,
+        attempt.language_id,
+        attempt.submitted_at,
+    )
+    .execute(db)
+    .await
+    .expect("attempt inserted");
+
+    attempt
+}
+
+/// A queued runner of the test for the attempt.
+pub async fn runner(db: &SqlitePool, attempt: &Attempt, test_id: Uuid) -> Runner {
+    let runner = blueprint().pending_runner(test_id, attempt.id);
+
+    runner.insert_new(db).await.expect("runner inserted");
+
+    runner
+}
+
+pub fn blueprint() -> NSJailBlueprint {
+    NSJailBlueprint {
+        tests: "/srv/tests".into(),
+        memory_limit: 256,
+        time_limit: 2,
+        max_cpus: 1,
+        network: NetworkPolicy::Isolated,
+        rootfs: "/srv/rootfs/python".into(),
+        mountpoint: "/srv/submissions/attempt".into(),
+        build_dir: None,
+        command: "/usr/bin/python3 main.py".to_owned(),
+        write_stdin: true,
+        cpu: None,
+    }
+}
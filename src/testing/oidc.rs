@@ -0,0 +1,140 @@
+//! A local OIDC identity provider, so logins can be tested end to end without the network.
+//!
+//! It skips the consent screen: the test follows the redirect to the provider itself and hands
+//! the nonce it finds there to the callback as the authorization code.
+
+use std::sync::Arc;
+
+use axum::{
+    Form, Json, Router,
+    extract::State,
+    routing::{get, post},
+};
+use chrono::{TimeDelta, Utc};
+use openidconnect::{
+    AccessToken, Audience, AuthUrl, EmptyAdditionalClaims, EmptyAdditionalProviderMetadata,
+    EmptyExtraTokenFields, EndUserEmail, EndUserName, IssuerUrl, JsonWebKeyId, JsonWebKeySetUrl,
+    Nonce, PrivateSigningKey, ResponseTypes, StandardClaims, SubjectIdentifier, TokenUrl,
+    core::{
+        CoreIdToken, CoreIdTokenClaims, CoreIdTokenFields, CoreJsonWebKeySet,
+        CoreJwsSigningAlgorithm, CoreProviderMetadata, CoreResponseType, CoreRsaPrivateSigningKey,
+        CoreSubjectIdentifierType, CoreTokenResponse, CoreTokenType,
+    },
+};
+use serde::Deserialize;
+use tokio::net::TcpListener;
+
+/// Client the provider issues its tokens to.
+pub const CLIENT_ID: &str = "evaltor";
+
+/// Who signs in at the provider.
+#[derive(Clone)]
+pub struct Identity {
+    pub subject: &'static str,
+    pub email: &'static str,
+    pub name: &'static str,
+    /// The `email_verified` claim, left out when `None`
+    pub email_verified: Option<bool>,
+}
+
+struct Issuer {
+    url: String,
+    identity: Identity,
+}
+
+/// Serves a provider that signs `identity` in, returns the URL of its issuer.
+pub async fn issuer(identity: Identity) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("local port bound");
+    let url = format!(
+        "http://{}",
+        listener.local_addr().expect("local address known")
+    );
+
+    let router = Router::new()
+        .route("/.well-known/openid-configuration", get(discovery))
+        .route("/jwks", get(jwks))
+        .route("/token", post(token))
+        .with_state(Arc::new(Issuer {
+            url: url.clone(),
+            identity,
+        }));
+
+    tokio::spawn(async move { axum::serve(listener, router).await });
+
+    url
+}
+
+fn signing_key() -> CoreRsaPrivateSigningKey {
+    CoreRsaPrivateSigningKey::from_pem(
+        include_str!("oidc-key.pem"),
+        Some(JsonWebKeyId::new("test".to_owned())),
+    )
+    .expect("valid signing key")
+}
+
+async fn discovery(State(issuer): State<Arc<Issuer>>) -> Json<CoreProviderMetadata> {
+    let url = |path: &str| format!("{}{path}", issuer.url);
+
+    let metadata = CoreProviderMetadata::new(
+        IssuerUrl::new(issuer.url.clone()).expect("valid issuer url"),
+        AuthUrl::new(url("/authorize")).expect("valid url"),
+        JsonWebKeySetUrl::new(url("/jwks")).expect("valid url"),
+        vec![ResponseTypes::new(vec![CoreResponseType::Code])],
+        vec![CoreSubjectIdentifierType::Public],
+        vec![CoreJwsSigningAlgorithm::RsaSsaPkcs1V15Sha256],
+        EmptyAdditionalProviderMetadata {},
+    )
+    .set_token_endpoint(Some(TokenUrl::new(url("/token")).expect("valid url")));
+
+    Json(metadata)
+}
+
+async fn jwks() -> Json<CoreJsonWebKeySet> {
+    Json(CoreJsonWebKeySet::new(vec![
+        signing_key().as_verification_key(),
+    ]))
+}
+
+#[derive(Deserialize)]
+struct TokenRequest {
+    /// The nonce of the login, see the module docs
+    code: String,
+}
+
+async fn token(
+    State(issuer): State<Arc<Issuer>>,
+    Form(request): Form<TokenRequest>,
+) -> Json<CoreTokenResponse> {
+    let identity = &issuer.identity;
+    let now = Utc::now();
+
+    let claims = CoreIdTokenClaims::new(
+        IssuerUrl::new(issuer.url.clone()).expect("valid issuer url"),
+        vec![Audience::new(CLIENT_ID.to_owned())],
+        now + TimeDelta::minutes(5),
+        now,
+        StandardClaims::new(SubjectIdentifier::new(identity.subject.to_owned()))
+            .set_email(Some(EndUserEmail::new(identity.email.to_owned())))
+            .set_email_verified(identity.email_verified)
+            .set_name(Some(EndUserName::new(identity.name.to_owned()).into())),
+        EmptyAdditionalClaims {},
+    )
+    .set_nonce(Some(Nonce::new(request.code)));
+
+    let id_token = CoreIdToken::new(
+        claims,
+        &signing_key(),
+        CoreJwsSigningAlgorithm::RsaSsaPkcs1V15Sha256,
+        None,
+        None,
+    )
+    .expect("id token signed");
+
+    Json(CoreTokenResponse::new(
+        AccessToken::new("access".to_owned()),
+        CoreTokenType::Bearer,
+        CoreIdTokenFields::new(Some(id_token), EmptyExtraTokenFields {}),
+    ))
+}
@@ -0,0 +1,277 @@
+use std::io;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::Parser;
+use reqwest::StatusCode;
+use tokio::fs;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+
+use crate::models::JobKind;
+use crate::sandbox::{Sandbox, SandboxArgs, SandboxOutput};
+use crate::worker::{
+    ClaimRequest, Heartbeat, WorkFile, WorkFiles, WorkItem, WorkResult, read_files, write_files,
+};
+
+/// How often a running job is reported alive, and asked whether it should keep running.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+/// How long to wait before talking to the server again after it could not be reached.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug, Parser)]
+pub struct WorkerArgs {
+    /// URL of the evaltor server, e.g. `http://127.0.0.1:3000`
+    #[clap(long, env = "EVALTOR_SERVER")]
+    pub server: String,
+
+    /// Secret shared with the server, see its `--worker-secret`
+    #[clap(long, env = "EVALTOR_WORKER_SECRET")]
+    pub worker_secret: String,
+
+    /// Name the server records on the jobs this worker runs
+    #[clap(long, env = "EVALTOR_WORKER_NAME", default_value = "evaltor-worker")]
+    pub name: String,
+
+    /// Directory the workspaces of running jobs are unpacked in
+    #[clap(
+        long,
+        env = "EVALTOR_WORKER_DIR",
+        default_value = "/var/lib/evaltor/worker"
+    )]
+    pub work_dir: PathBuf,
+
+    /// Number of jobs run at the same time
+    #[clap(long, env = "EVALTOR_WORKER_SLOTS", default_value = "1")]
+    pub slots: NonZeroUsize,
+
+    #[clap(flatten)]
+    pub sandbox: SandboxArgs,
+}
+
+/// Pulls jobs from the server and runs them until `shutdown` completes.
+///
+/// The jobs running then are killed and left to the server, which claims them again once
+/// their leases run out.
+pub async fn run_worker(args: WorkerArgs, shutdown: impl Future<Output = ()>) -> io::Result<()> {
+    fs::create_dir_all(&args.work_dir).await?;
+
+    let (stop, stopped) = watch::channel(false);
+
+    let worker = Arc::new(Worker {
+        http: reqwest::Client::new(),
+        server: args.server.trim_end_matches('/').to_owned(),
+        sandbox: args.sandbox.build(),
+        stopped,
+        args,
+    });
+
+    let mut slots = JoinSet::new();
+
+    for _ in 0..worker.args.slots.get() {
+        slots.spawn(worker.clone().slot());
+    }
+
+    shutdown.await;
+    stop.send_replace(true);
+
+    while slots.join_next().await.is_some() {}
+
+    Ok(())
+}
+
+#[derive(Debug)]
+struct Worker {
+    http: reqwest::Client,
+    server: String,
+    sandbox: Arc<dyn Sandbox>,
+    /// Turns `true` once the worker should exit
+    stopped: watch::Receiver<bool>,
+    args: WorkerArgs,
+}
+
+impl Worker {
+    async fn slot(self: Arc<Self>) {
+        let mut stopped = self.stopped.clone();
+
+        while !*stopped.borrow() {
+            let claim = tokio::select! {
+                claim = self.claim() => claim,
+                _ = stopped.changed() => return,
+            };
+
+            match claim {
+                Ok(Some(item)) => self.process(item).await,
+                Ok(None) => {}
+                Err(err) => {
+                    eprintln!("Failed to claim job: {err}");
+                    tokio::time::sleep(RETRY_INTERVAL).await;
+                }
+            }
+        }
+    }
+
+    fn post(&self, path: &str) -> reqwest::RequestBuilder {
+        self.http
+            .post(format!("{}{path}", self.server))
+            .bearer_auth(&self.args.worker_secret)
+    }
+
+    /// Waits for the next job, `None` if the server had nothing before the long poll ended.
+    async fn claim(&self) -> reqwest::Result<Option<WorkItem>> {
+        let response = self
+            .post("/worker/claim")
+            .json(&ClaimRequest {
+                worker: self.args.name.clone(),
+            })
+            .send()
+            .await?
+            .error_for_status()?;
+
+        if response.status() == StatusCode::NO_CONTENT {
+            return Ok(None);
+        }
+
+        response.json().await.map(Some)
+    }
+
+    /// Runs the job and reports its outcome, whatever it was.
+    async fn process(&self, item: WorkItem) {
+        let workspace = self.args.work_dir.join(item.job_id.to_string());
+
+        let output = self.execute(&item, &workspace).await;
+
+        _ = fs::remove_dir_all(&workspace).await;
+
+        if *self.stopped.borrow() {
+            eprintln!(
+                "Dropping job {}, the server runs it again once its lease runs out",
+                item.job_id
+            );
+            return;
+        }
+
+        let (output, build) = match output {
+            Ok((output, build)) => (Ok(output), build),
+            Err(reason) => (Err(reason), Vec::new()),
+        };
+
+        let result = WorkResult {
+            tries: item.tries,
+            output,
+            build,
+        };
+
+        let response = self
+            .post(&format!("/worker/jobs/{}/result", item.job_id))
+            .json(&result)
+            .send()
+            .await;
+
+        match response {
+            Ok(response) if response.status() == StatusCode::CONFLICT => {
+                eprintln!(
+                    "Job {} was cancelled or taken over, dropping its result",
+                    item.job_id
+                );
+            }
+            Ok(response) => {
+                if let Err(err) = response.error_for_status() {
+                    eprintln!("Failed to upload result of job {}: {err}", item.job_id);
+                }
+            }
+            Err(err) => eprintln!("Failed to upload result of job {}: {err}", item.job_id),
+        }
+    }
+
+    /// Downloads the job's files into `workspace` and runs it there, heartbeating meanwhile.
+    async fn execute(
+        &self,
+        item: &WorkItem,
+        workspace: &std::path::Path,
+    ) -> Result<(SandboxOutput, Vec<WorkFile>), String> {
+        let files: WorkFiles = self
+            .http
+            .get(format!("{}/worker/jobs/{}/files", self.server, item.job_id))
+            .bearer_auth(&self.args.worker_secret)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|err| format!("failed to download files: {err}"))?
+            .json()
+            .await
+            .map_err(|err| format!("failed to download files: {err}"))?;
+
+        let build = workspace.join("build");
+
+        fs::create_dir_all(&build)
+            .await
+            .map_err(|err| format!("failed to create workspace: {err}"))?;
+
+        write_files(workspace, &files.workspace)
+            .await
+            .map_err(|err| format!("failed to unpack workspace: {err}"))?;
+
+        let blueprint = item.blueprint(&self.args.sandbox.rootfs, workspace);
+        let (cancel, signal) = watch::channel(false);
+        let mut stopped = self.stopped.clone();
+
+        let run = self
+            .sandbox
+            .run(blueprint, item.run_id, files.stdin, signal);
+        tokio::pin!(run);
+
+        let output = loop {
+            tokio::select! {
+                output = &mut run => break output,
+                () = tokio::time::sleep(HEARTBEAT_INTERVAL) => {
+                    if !self.heartbeat(item).await {
+                        cancel.send_replace(true);
+                    }
+                }
+                Ok(()) = stopped.changed() => {
+                    cancel.send_replace(true);
+                    break (&mut run).await;
+                }
+            }
+        };
+
+        let output = output.map_err(|err| format!("failed to run sandbox: {err}"))?;
+
+        let build = match item.kind {
+            JobKind::Compile => read_files(&build)
+                .await
+                .map_err(|err| format!("failed to read build output: {err}"))?,
+            JobKind::Test => Vec::new(),
+        };
+
+        Ok((output, build))
+    }
+
+    /// Extends the job's lease, returns whether it should keep running.
+    ///
+    /// A server that cannot be reached is assumed to still want the job.
+    async fn heartbeat(&self, item: &WorkItem) -> bool {
+        let response = self
+            .post(&format!("/worker/jobs/{}/heartbeat", item.job_id))
+            .json(&Heartbeat { tries: item.tries })
+            .send()
+            .await;
+
+        match response {
+            Ok(response) if response.status() == StatusCode::CONFLICT => false,
+            Ok(response) => {
+                if let Err(err) = response.error_for_status() {
+                    eprintln!("Failed to extend lease of job {}: {err}", item.job_id);
+                }
+                true
+            }
+            Err(err) => {
+                eprintln!("Failed to extend lease of job {}: {err}", item.job_id);
+                true
+            }
+        }
+    }
+}
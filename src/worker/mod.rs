@@ -0,0 +1,225 @@
+//! Judging on other machines: the protocol `evaltor-worker` speaks with the server's
+//! `/worker` routes, and the worker itself.
+//!
+//! A worker claims a job, downloads the attempt's workspace and the test's input, runs the
+//! blueprint in its own sandbox and uploads the raw output. Verdicts are decided by the
+//! server, so the expected outputs never leave it.
+
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use uuid::Uuid;
+
+use crate::models::{Job, JobKind, NetworkPolicy};
+use crate::nsjail::{BuildMount, NSJailBlueprint};
+use crate::sandbox::SandboxOutput;
+
+pub use client::{WorkerArgs, run_worker};
+
+mod client;
+
+/// Body of `POST /worker/claim`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ClaimRequest {
+    /// Name of the worker, recorded on the job it gets
+    pub worker: String,
+}
+
+/// A claimed job, everything a worker needs to run it besides the files.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WorkItem {
+    pub job_id: Uuid,
+    /// Which try of the job this is, only the latest try may report back
+    pub tries: i64,
+    pub kind: JobKind,
+    /// Compilation or runner the job fills in
+    pub run_id: Uuid,
+
+    /// Root filesystem of the language, relative to the worker's `--rootfs`
+    pub rootfs: String,
+    pub command: String,
+    pub write_stdin: bool,
+
+    pub memory_limit: i64,
+    pub time_limit: i64,
+    pub max_cpus: i64,
+    pub network: NetworkPolicy,
+}
+
+impl WorkItem {
+    pub fn new(job: &Job, run_id: Uuid, rootfs: String, blueprint: &NSJailBlueprint) -> Self {
+        Self {
+            job_id: job.id,
+            tries: job.tries,
+            kind: job.kind,
+            run_id,
+            rootfs,
+            command: blueprint.command.clone(),
+            write_stdin: blueprint.write_stdin,
+            memory_limit: blueprint.memory_limit,
+            time_limit: blueprint.time_limit,
+            max_cpus: blueprint.max_cpus,
+            network: blueprint.network,
+        }
+    }
+
+    /// Blueprint running the job in `workspace`, a local copy of the attempt's directory.
+    pub fn blueprint(&self, rootfs: &Path, workspace: &Path) -> NSJailBlueprint {
+        let build = workspace.join("build");
+
+        NSJailBlueprint {
+            // judged by the server, the worker never sees the expected outputs
+            tests: PathBuf::new(),
+            memory_limit: self.memory_limit,
+            time_limit: self.time_limit,
+            max_cpus: self.max_cpus,
+            network: self.network,
+            rootfs: rootfs.join(&self.rootfs),
+            mountpoint: workspace.to_owned(),
+            build_dir: Some(match self.kind {
+                JobKind::Compile => BuildMount::Writable(build),
+                JobKind::Test => BuildMount::ReadOnly(build),
+            }),
+            command: self.command.clone(),
+            write_stdin: self.write_stdin,
+            cpu: None,
+        }
+    }
+}
+
+/// Response of `GET /worker/jobs/{id}/files`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WorkFiles {
+    /// The attempt's directory, including the build output for test jobs
+    pub workspace: Vec<WorkFile>,
+    #[serde(with = "base64_bytes")]
+    pub stdin: Vec<u8>,
+}
+
+/// A regular file, with its path relative to the directory it was read from.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WorkFile {
+    pub path: String,
+    pub executable: bool,
+    #[serde(with = "base64_bytes")]
+    pub contents: Vec<u8>,
+}
+
+/// Body of `POST /worker/jobs/{id}/heartbeat`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Heartbeat {
+    pub tries: i64,
+}
+
+/// Body of `POST /worker/jobs/{id}/result`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WorkResult {
+    pub tries: i64,
+    /// Output of the sandbox, or why the worker could not run the job
+    pub output: Result<SandboxOutput, String>,
+    /// What a compile job left in `/workspace/build`
+    pub build: Vec<WorkFile>,
+}
+
+/// Reads every regular file below `root`, symlinks and other special files are skipped.
+pub async fn read_files(root: &Path) -> io::Result<Vec<WorkFile>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![root.to_owned()];
+
+    while let Some(dir) = dirs.pop() {
+        let mut entries = fs::read_dir(&dir).await?;
+
+        while let Some(entry) = entries.next_entry().await? {
+            let file_type = entry.file_type().await?;
+            let path = entry.path();
+
+            if file_type.is_dir() {
+                dirs.push(path);
+            } else if file_type.is_file() {
+                let executable = entry.metadata().await?.permissions().mode() & 0o111 != 0;
+                let relative = path.strip_prefix(root).map_err(io::Error::other)?;
+
+                files.push(WorkFile {
+                    path: relative.to_string_lossy().into_owned(),
+                    executable,
+                    contents: fs::read(&path).await?,
+                });
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// Writes the files below `root`, refusing any path that would end up outside of it.
+pub async fn write_files(root: &Path, files: &[WorkFile]) -> io::Result<()> {
+    for file in files {
+        let relative = Path::new(&file.path);
+
+        if !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("refusing to write {}", file.path),
+            ));
+        }
+
+        let path = root.join(relative);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        fs::write(&path, &file.contents).await?;
+
+        if file.executable {
+            fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Byte buffers as base64 strings, JSON arrays of numbers would be several times larger.
+pub mod base64_bytes {
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD;
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+
+        STANDARD.decode(encoded).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WorkFile, write_files};
+
+    #[tokio::test]
+    async fn write_files_stays_inside_root() {
+        let root = std::env::temp_dir().join(format!("evaltor-test-{}", uuid::Uuid::new_v4()));
+
+        let file = |path: &str| WorkFile {
+            path: path.to_owned(),
+            executable: false,
+            contents: b"x".to_vec(),
+        };
+
+        assert!(write_files(&root, &[file("nested/main")]).await.is_ok());
+        assert!(write_files(&root, &[file("../escaped")]).await.is_err());
+        assert!(write_files(&root, &[file("/etc/escaped")]).await.is_err());
+
+        _ = tokio::fs::remove_dir_all(&root).await;
+    }
+}